@@ -0,0 +1,334 @@
+//! The ethash algorithm: per-epoch cache generation, dataset items derived
+//! from the cache, and hashimoto over either.
+
+use keccak::{keccak256, keccak512};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+pub const EPOCH_LENGTH: u64 = 30_000;
+const CACHE_BYTES_INIT: u64 = 1 << 24;
+const CACHE_BYTES_GROWTH: u64 = 1 << 17;
+const DATASET_BYTES_INIT: u64 = 1 << 30;
+const DATASET_BYTES_GROWTH: u64 = 1 << 23;
+const HASH_BYTES: usize = 64;
+const MIX_BYTES: usize = 128;
+const MIX_WORDS: usize = MIX_BYTES / 4;
+const MIX_NODES: usize = MIX_BYTES / HASH_BYTES;
+const NODE_WORDS: usize = HASH_BYTES / 4;
+const DATASET_PARENTS: u32 = 256;
+const CACHE_ROUNDS: usize = 3;
+const ACCESSES: u32 = 64;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// 64 bytes of cache or dataset.
+pub type Node = [u8; HASH_BYTES];
+
+/// Result of hashimoto, `value` is compared against the boundary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofOfWork {
+    pub value: [u8; 32],
+    pub mix_hash: [u8; 32],
+}
+
+pub fn epoch(block_number: u64) -> u64 {
+    block_number / EPOCH_LENGTH
+}
+
+pub fn seed_hash(epoch: u64) -> [u8; 32] {
+    let mut seed = [0u8; 32];
+    for _ in 0..epoch {
+        seed = keccak256(&seed);
+    }
+    seed
+}
+
+pub fn cache_size(epoch: u64) -> usize {
+    let mut size = CACHE_BYTES_INIT + CACHE_BYTES_GROWTH * epoch - HASH_BYTES as u64;
+    while !is_prime(size / HASH_BYTES as u64) {
+        size -= 2 * HASH_BYTES as u64;
+    }
+    size as usize
+}
+
+pub fn dataset_size(epoch: u64) -> u64 {
+    let mut size = DATASET_BYTES_INIT + DATASET_BYTES_GROWTH * epoch - MIX_BYTES as u64;
+    while !is_prime(size / MIX_BYTES as u64) {
+        size -= 2 * MIX_BYTES as u64;
+    }
+    size
+}
+
+fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    let mut i = 2;
+    while i * i <= n {
+        if n.is_multiple_of(i) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+fn fnv(a: u32, b: u32) -> u32 {
+    a.wrapping_mul(FNV_PRIME) ^ b
+}
+
+fn word(bytes: &[u8], index: usize) -> u32 {
+    let i = index * 4;
+    u32::from(bytes[i])
+        | u32::from(bytes[i + 1]) << 8
+        | u32::from(bytes[i + 2]) << 16
+        | u32::from(bytes[i + 3]) << 24
+}
+
+fn set_word(bytes: &mut [u8], index: usize, value: u32) {
+    let i = index * 4;
+    bytes[i] = value as u8;
+    bytes[i + 1] = (value >> 8) as u8;
+    bytes[i + 2] = (value >> 16) as u8;
+    bytes[i + 3] = (value >> 24) as u8;
+}
+
+/// The light cache of one epoch, enough to verify a seal without the
+/// full dataset.
+pub struct Light {
+    epoch: u64,
+    cache: Vec<Node>,
+    dataset_size: u64,
+}
+
+impl Light {
+    pub fn new(epoch: u64) -> Self {
+        Light::with_sizes(epoch, cache_size(epoch), dataset_size(epoch))
+    }
+
+    /// A cache with non-standard sizes, only useful for dev chains where
+    /// the seal just has to be cheap and self-consistent.
+    pub fn with_sizes(epoch: u64, cache_size: usize, dataset_size: u64) -> Self {
+        let nodes = cache_size / HASH_BYTES;
+        let mut cache: Vec<Node> = Vec::with_capacity(nodes);
+        cache.push(keccak512(&seed_hash(epoch)));
+        for i in 1..nodes {
+            let next = keccak512(&cache[i - 1]);
+            cache.push(next);
+        }
+
+        for _ in 0..CACHE_ROUNDS {
+            for i in 0..nodes {
+                let v = word(&cache[i], 0) as usize % nodes;
+                let mut data = cache[(i + nodes - 1) % nodes];
+                for (byte, other) in data.iter_mut().zip(cache[v].iter()) {
+                    *byte ^= *other;
+                }
+                cache[i] = keccak512(&data);
+            }
+        }
+        Light {
+            epoch,
+            cache,
+            dataset_size,
+        }
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn dataset_size(&self) -> u64 {
+        self.dataset_size
+    }
+
+    /// Reads a cache written by `to_file`, rejecting files of the wrong size.
+    pub fn from_file(path: &Path, epoch: u64) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        if bytes.len() != cache_size(epoch) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "ethash cache has the wrong size",
+            ));
+        }
+        let cache = bytes
+            .chunks(HASH_BYTES)
+            .map(|chunk| {
+                let mut node = [0u8; HASH_BYTES];
+                node.copy_from_slice(chunk);
+                node
+            })
+            .collect();
+        Ok(Light {
+            epoch,
+            cache,
+            dataset_size: dataset_size(epoch),
+        })
+    }
+
+    /// Writes the cache through a temporary file so a crash never leaves a
+    /// truncated cache behind.
+    pub fn to_file(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        {
+            let mut file = File::create(&tmp)?;
+            for node in &self.cache {
+                file.write_all(node)?;
+            }
+            file.sync_all()?;
+        }
+        fs::rename(tmp, path)
+    }
+
+    pub fn dataset_item(&self, index: u32) -> Node {
+        let nodes = self.cache.len();
+        let mut mix = self.cache[index as usize % nodes];
+        let first = word(&mix, 0) ^ index;
+        set_word(&mut mix, 0, first);
+        mix = keccak512(&mix);
+
+        for j in 0..DATASET_PARENTS {
+            let parent_index = fnv(index ^ j, word(&mix, j as usize % NODE_WORDS)) as usize % nodes;
+            let parent = &self.cache[parent_index];
+            for w in 0..NODE_WORDS {
+                let value = fnv(word(&mix, w), word(parent, w));
+                set_word(&mut mix, w, value);
+            }
+        }
+        keccak512(&mix)
+    }
+
+    /// Hashimoto with dataset items derived from the cache on demand.
+    pub fn compute(&self, pow_hash: &[u8; 32], nonce: u64) -> ProofOfWork {
+        hashimoto(pow_hash, nonce, self.dataset_size, |index| {
+            self.dataset_item(index)
+        })
+    }
+}
+
+/// The full dataset of one epoch. On mainnet it is over 1GB and slow to
+/// generate, but hashing against it is far faster than against the cache.
+pub struct Full {
+    epoch: u64,
+    dataset: Vec<Node>,
+}
+
+impl Full {
+    pub fn new(light: &Light) -> Self {
+        let items = (light.dataset_size / HASH_BYTES as u64) as u32;
+        Full {
+            epoch: light.epoch,
+            dataset: (0..items).map(|i| light.dataset_item(i)).collect(),
+        }
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn compute(&self, pow_hash: &[u8; 32], nonce: u64) -> ProofOfWork {
+        let size = self.dataset.len() as u64 * HASH_BYTES as u64;
+        hashimoto(pow_hash, nonce, size, |index| self.dataset[index as usize])
+    }
+}
+
+/// keccak512 of the pow hash followed by the little-endian nonce.
+fn hashimoto_seed(pow_hash: &[u8; 32], nonce: u64) -> Node {
+    let mut input = [0u8; 40];
+    input[..32].copy_from_slice(pow_hash);
+    for i in 0..8 {
+        input[32 + i] = (nonce >> (8 * i)) as u8;
+    }
+    keccak512(&input)
+}
+
+fn final_value(seed: &Node, mix_hash: &[u8; 32]) -> [u8; 32] {
+    let mut input = [0u8; HASH_BYTES + 32];
+    input[..HASH_BYTES].copy_from_slice(seed);
+    input[HASH_BYTES..].copy_from_slice(mix_hash);
+    keccak256(&input)
+}
+
+fn hashimoto<F: Fn(u32) -> Node>(
+    pow_hash: &[u8; 32],
+    nonce: u64,
+    full_size: u64,
+    lookup: F,
+) -> ProofOfWork {
+    let seed = hashimoto_seed(pow_hash, nonce);
+
+    let mut mix = [0u8; MIX_BYTES];
+    mix[..HASH_BYTES].copy_from_slice(&seed);
+    mix[HASH_BYTES..].copy_from_slice(&seed);
+
+    let rows = (full_size / MIX_BYTES as u64) as u32;
+    let seed_head = word(&seed, 0);
+    for i in 0..ACCESSES {
+        let p = fnv(i ^ seed_head, word(&mix, i as usize % MIX_WORDS)) % rows * MIX_NODES as u32;
+        for n in 0..MIX_NODES {
+            let node = lookup(p + n as u32);
+            for w in 0..NODE_WORDS {
+                let index = n * NODE_WORDS + w;
+                let value = fnv(word(&mix, index), word(&node, w));
+                set_word(&mut mix, index, value);
+            }
+        }
+    }
+
+    let mut mix_hash = [0u8; 32];
+    for i in 0..MIX_WORDS / 4 {
+        let compressed = fnv(
+            fnv(
+                fnv(word(&mix, 4 * i), word(&mix, 4 * i + 1)),
+                word(&mix, 4 * i + 2),
+            ),
+            word(&mix, 4 * i + 3),
+        );
+        set_word(&mut mix_hash, i, compressed);
+    }
+    ProofOfWork {
+        value: final_value(&seed, &mix_hash),
+        mix_hash,
+    }
+}
+
+/// The final hashimoto value given a claimed mix hash, cheap enough to reject
+/// most bad seals before computing the real mix.
+pub fn quick_get_difficulty(pow_hash: &[u8; 32], nonce: u64, mix_hash: &[u8; 32]) -> [u8; 32] {
+    final_value(&hashimoto_seed(pow_hash, nonce), mix_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cache_size, dataset_size, epoch, seed_hash, Full, Light};
+
+    #[test]
+    fn test_sizes() {
+        assert_eq!(cache_size(0), 16_776_896);
+        assert_eq!(cache_size(1), 16_907_456);
+        assert_eq!(dataset_size(0), 1_073_739_904);
+        assert_eq!(dataset_size(1), 1_082_130_304);
+        assert_eq!(epoch(29_999), 0);
+        assert_eq!(epoch(30_000), 1);
+    }
+
+    #[test]
+    fn test_seed_hash() {
+        assert_eq!(seed_hash(0), [0u8; 32]);
+        assert_eq!(seed_hash(1)[..4], [0x29, 0x0d, 0xec, 0xd9]);
+    }
+
+    #[test]
+    fn test_full_matches_light() {
+        let light = Light::with_sizes(0, 1024, 32 * 1024);
+        let full = Full::new(&light);
+        let pow_hash = [7u8; 32];
+        for nonce in 0..4 {
+            assert_eq!(
+                full.compute(&pow_hash, nonce),
+                light.compute(&pow_hash, nonce)
+            );
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use keccak_hash::{keccak_256, keccak_512};
+
+pub fn keccak256(input: &[u8]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    unsafe {
+        keccak_256(out.as_mut_ptr(), out.len(), input.as_ptr(), input.len());
+    }
+    out
+}
+
+pub fn keccak512(input: &[u8]) -> [u8; 64] {
+    let mut out = [0u8; 64];
+    unsafe {
+        keccak_512(out.as_mut_ptr(), out.len(), input.as_ptr(), input.len());
+    }
+    out
+}
@@ -0,0 +1,187 @@
+//! Ethash proof of work: light cache generation, hashimoto and seal
+//! verification helpers, with light caches kept in memory and on disk.
+
+extern crate ethereum_types;
+extern crate keccak_hash;
+
+#[cfg(test)]
+extern crate tempdir;
+
+mod compute;
+mod keccak;
+
+use ethereum_types::{H256, U256};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+pub use compute::{
+    cache_size, dataset_size, epoch, quick_get_difficulty, seed_hash, Full, Light, Node,
+    ProofOfWork, EPOCH_LENGTH,
+};
+
+/// Light caches kept in memory, the current epoch and the one before.
+const MEMORY_CACHE_SIZE: usize = 2;
+/// Dev sizes, small enough that a full dataset builds instantly.
+const DEV_CACHE_SIZE: usize = 1024;
+const DEV_DATASET_SIZE: u64 = 32 * 1024;
+
+/// Hands out light caches, building them at most once per epoch. With a
+/// cache directory they also survive restarts.
+pub struct EthashManager {
+    cache_dir: Option<PathBuf>,
+    dev: bool,
+    lights: Mutex<Vec<Arc<Light>>>,
+}
+
+impl EthashManager {
+    pub fn new(cache_dir: Option<PathBuf>) -> Self {
+        EthashManager {
+            cache_dir,
+            dev: false,
+            lights: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Tiny caches and datasets that are not mainnet compatible, seals
+    /// only verify against another dev manager.
+    pub fn dev() -> Self {
+        EthashManager {
+            cache_dir: None,
+            dev: true,
+            lights: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Light cache of the epoch `block_number` belongs to.
+    pub fn light(&self, block_number: u64) -> Arc<Light> {
+        let epoch = epoch(block_number);
+        if let Some(light) = self.cached(epoch) {
+            return light;
+        }
+
+        // building takes seconds, don't hold up lookups of other epochs;
+        // two threads may race to build the same epoch and the first one in
+        // wins
+        let light = Arc::new(self.load_or_build(epoch));
+        let mut lights = self.lights.lock().expect("ethash cache lock poisoned");
+        if let Some(light) = lights.iter().find(|light| light.epoch() == epoch) {
+            return light.clone();
+        }
+        if lights.len() == MEMORY_CACHE_SIZE {
+            lights.remove(0);
+        }
+        lights.push(light.clone());
+        light
+    }
+
+    fn cached(&self, epoch: u64) -> Option<Arc<Light>> {
+        self.lights
+            .lock()
+            .expect("ethash cache lock poisoned")
+            .iter()
+            .find(|light| light.epoch() == epoch)
+            .cloned()
+    }
+
+    /// Hashimoto-light of a header's bare hash and nonce.
+    pub fn compute_light(&self, block_number: u64, pow_hash: &H256, nonce: u64) -> ProofOfWork {
+        self.light(block_number).compute(&pow_hash.0, nonce)
+    }
+
+    fn load_or_build(&self, epoch: u64) -> Light {
+        if self.dev {
+            return Light::with_sizes(epoch, DEV_CACHE_SIZE, DEV_DATASET_SIZE);
+        }
+        let path = match self.cache_dir {
+            Some(ref dir) => dir.join(format!("{:?}", H256::from(seed_hash(epoch)))),
+            None => return Light::new(epoch),
+        };
+        if let Ok(light) = Light::from_file(&path, epoch) {
+            return light;
+        }
+        let light = Light::new(epoch);
+        // the cache is only an optimisation, failing to persist it is not fatal
+        let _ = fs::create_dir_all(path.parent().expect("joined onto the cache dir"))
+            .and_then(|_| light.to_file(&path));
+        light
+    }
+}
+
+/// The largest hashimoto value a seal may have at `difficulty`, 2^256 / difficulty.
+pub fn difficulty_to_boundary(difficulty: &U256) -> H256 {
+    invert(*difficulty).into()
+}
+
+pub fn boundary_to_difficulty(boundary: &H256) -> U256 {
+    invert(U256::from(*boundary))
+}
+
+/// 2^256 / x, saturating at 2^256 - 1.
+fn invert(x: U256) -> U256 {
+    if x <= U256::one() {
+        U256::max_value()
+    } else {
+        let (quotient, remainder) = (U256::max_value() / x, U256::max_value() % x);
+        if remainder + U256::one() == x {
+            quotient + U256::one()
+        } else {
+            quotient
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        boundary_to_difficulty, difficulty_to_boundary, quick_get_difficulty, EthashManager, Light,
+    };
+    use ethereum_types::{H256, U256};
+    use tempdir::TempDir;
+
+    /// Mainnet block 486382.
+    fn fixture() -> (u64, H256, u64, H256, U256) {
+        (
+            486_382,
+            "f57e6f3acfc0dd4b5bf2bee40ab3358aa68773a8d09f5e595eab559405527d72".into(),
+            0xd7b3ac70a301a249,
+            "1fff04cec94173fd591e3d8960ce6bdf8b1971048c71ff937bb2d32a6431ab6d".into(),
+            U256::from(0x0856_5725_4bd9u64),
+        )
+    }
+
+    #[test]
+    fn test_light_compute() {
+        let (number, pow_hash, nonce, mix_hash, difficulty) = fixture();
+        let dir = TempDir::new("ethash").unwrap();
+        let manager = EthashManager::new(Some(dir.path().to_owned()));
+        let pow = manager.compute_light(number, &pow_hash, nonce);
+        assert_eq!(H256::from(pow.mix_hash), mix_hash);
+        assert!(H256::from(pow.value) <= difficulty_to_boundary(&difficulty));
+        assert_eq!(
+            quick_get_difficulty(&pow_hash.0, nonce, &pow.mix_hash),
+            pow.value
+        );
+        assert!(manager.compute_light(number, &pow_hash, nonce + 1).mix_hash != pow.mix_hash);
+
+        // the cache was persisted and reads back identically
+        let epoch = super::epoch(number);
+        let path = dir
+            .path()
+            .join(format!("{:?}", H256::from(super::seed_hash(epoch))));
+        let reloaded = Light::from_file(&path, epoch).unwrap();
+        assert_eq!(reloaded.compute(&pow_hash.0, nonce), pow);
+    }
+
+    #[test]
+    fn test_boundary() {
+        let difficulty = U256::from(0x20000);
+        let boundary = difficulty_to_boundary(&difficulty);
+        assert_eq!(boundary, H256::from(U256::one() << (256 - 17)));
+        assert_eq!(boundary_to_difficulty(&boundary), difficulty);
+        assert_eq!(
+            difficulty_to_boundary(&U256::one()),
+            H256::from(U256::max_value())
+        );
+    }
+}
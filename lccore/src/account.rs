@@ -0,0 +1,82 @@
+use ethereum_types::{H256, U256};
+use keccak_hash::{KECCAK_EMPTY, KECCAK_NULL_RLP};
+use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
+
+/// An account as stored in the state trie under `keccak(address)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub nonce: u64,
+    pub balance: U256,
+    /// Root of the account's storage trie.
+    pub storage_root: H256,
+    pub code_hash: H256,
+}
+
+impl Default for Account {
+    fn default() -> Self {
+        Account {
+            nonce: 0,
+            balance: U256::zero(),
+            storage_root: KECCAK_NULL_RLP,
+            code_hash: KECCAK_EMPTY,
+        }
+    }
+}
+
+impl Account {
+    pub fn new(nonce: u64, balance: U256) -> Self {
+        Account {
+            nonce,
+            balance,
+            ..Account::default()
+        }
+    }
+
+    pub fn has_code(&self) -> bool {
+        self.code_hash != KECCAK_EMPTY
+    }
+}
+
+impl Encodable for Account {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4);
+        s.append(&self.nonce);
+        s.append(&self.balance);
+        s.append(&self.storage_root);
+        s.append(&self.code_hash);
+    }
+}
+
+impl Decodable for Account {
+    fn decode(rlp: &UntrustedRlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(Account {
+            nonce: rlp.val_at(0)?,
+            balance: rlp.val_at(1)?,
+            storage_root: rlp.val_at(2)?,
+            code_hash: rlp.val_at(3)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rlp;
+    use rustc_hex::FromHex;
+    use super::Account;
+
+    #[test]
+    fn test_account_rlp() {
+        // the empty account: zero nonce and balance, empty storage and code
+        let expected = "f8448080a056e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421a0c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470".from_hex().unwrap();
+        let mut account = Account::default();
+        assert_eq!(rlp::encode(&account).into_vec(), expected);
+
+        account.nonce = 3;
+        account.balance = 1_000_000_000_000_000_000u64.into();
+        let decoded: Account = rlp::decode(&rlp::encode(&account));
+        assert_eq!(decoded, account);
+    }
+}
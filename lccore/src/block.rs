@@ -1,53 +1,341 @@
 use bytes::Bytes;
-use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
+use ethereum_types::H256;
+use keccak_hash::keccak;
+use rlp::{self, Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
+use trie::ordered_trie_root;
+use error::BlockDecodeError;
 use header::Header;
-use transaction::Transaction;
+use transaction::UnverifiedTransaction;
 
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct Block {
     pub header: Header,
-    pub transactions: Vec<Transaction>,
+    pub transactions: Vec<UnverifiedTransaction>,
+    pub uncles: Vec<Header>,
 }
 
 impl Block {
+    /// The one block encoding, `Encodable` writes the same with the seal.
+    /// Without the seal the header is written bare, `decode_rlp` with the
+    /// same flag reads it back with an empty seal.
+    pub fn stream_rlp(&self, s: &mut RlpStream, seal: bool) {
+        s.begin_list(3);
+        self.header.stream_rlp(s, seal);
+        s.append_list(&self.transactions);
+        s.append_list(&self.uncles);
+    }
+
     pub fn rlp_bytes(&self, seal: bool) -> Bytes {
-        let mut buf = RlpStream::new_list(3);
-        self.header.stream_rlp(&mut buf, seal);
-        buf.append_list(&self.transactions);
-        buf.out()
+        let mut s = RlpStream::new();
+        self.stream_rlp(&mut s, seal);
+        s.out()
+    }
+
+    /// Decodes a block written by `stream_rlp(s, seal)`. Uncles are always
+    /// sealed.
+    pub fn decode_rlp(rlp: &UntrustedRlp, seal: bool) -> Result<Self, BlockDecodeError> {
+        let items = rlp.item_count()?;
+        if items != 3 {
+            return Err(BlockDecodeError::MismatchedLayout { items });
+        }
+        Ok(Block {
+            header: Header::decode_rlp(&rlp.at(0)?, seal)?,
+            transactions: rlp.list_at(1)?,
+            uncles: rlp.list_at(2)?,
+        })
+    }
+
+    /// The value `header.uncles_hash` must commit to.
+    pub fn compute_uncles_hash(&self) -> H256 {
+        keccak(rlp::encode_list(&self.uncles))
+    }
+
+    /// The value `header.transactions_root` must commit to, typed
+    /// transactions enter the trie as their EIP-2718 envelope.
+    pub fn compute_transactions_root(&self) -> H256 {
+        ordered_trie_root(self.transactions.iter().map(|tx| tx.envelope()))
     }
 }
 
 impl Encodable for Block {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(2);
-        s.append(&self.header);
-        s.append_list(&self.transactions);
+        self.stream_rlp(s, true);
     }
 }
 
 impl Decodable for Block {
+    /// Reads sealed blocks. Bare headers only decode without fork fields,
+    /// see `Header`'s `Decodable` impl.
     fn decode(rlp: &UntrustedRlp) -> Result<Self, DecoderError> {
-        if rlp.item_count()? != 2 {
+        if rlp.item_count()? != 3 {
             return Err(DecoderError::RlpIncorrectListLen);
         }
         Ok(Block {
             header: rlp.val_at(0)?,
             transactions: rlp.list_at(1)?,
+            uncles: rlp.list_at(2)?,
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use ethereum_types::{Address, H256, U256};
     use rustc_hex::FromHex;
-    use rlp;
+    use rlp::{self, DecoderError, RlpStream, UntrustedRlp};
+    use error::BlockDecodeError;
+    use header::{CancunFields, Header, LondonFields, ShanghaiFields};
     use super::Block;
+    use transaction::{Recipient, Transaction};
+    use typed_transaction::{AccessListItem, AccessListTransaction, DynamicFeeTransaction, TypedTransaction};
+
+    /// xorshift64, enough to generate arbitrary blocks without pulling in a
+    /// property testing crate.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        fn u256(&mut self) -> U256 {
+            // mix small and full width values, small ones hit the short encodings
+            match self.below(3) {
+                0 => U256::from(self.below(256)),
+                1 => U256::from(self.next()),
+                _ => U256::from(self.h256()),
+            }
+        }
+
+        fn h256(&mut self) -> H256 {
+            let mut bytes = [0u8; 32];
+            for byte in bytes.iter_mut() {
+                *byte = self.next() as u8;
+            }
+            H256::from(bytes)
+        }
+
+        fn bytes(&mut self) -> Vec<u8> {
+            let len = self.below(70);
+            (0..len).map(|_| self.next() as u8).collect()
+        }
+
+        fn recipient(&mut self) -> Recipient {
+            if self.below(4) == 0 {
+                Recipient::create()
+            } else {
+                Recipient::call(Address::from(self.h256()))
+            }
+        }
+
+        fn header(&mut self, sealed: bool) -> Header {
+            let mut header = Header {
+                parent_hash: self.h256(),
+                uncles_hash: self.h256(),
+                coinbase: Address::from(self.h256()),
+                state_root: self.h256(),
+                transactions_root: self.h256(),
+                receipts_root: self.h256(),
+                difficulty: self.u256(),
+                number: self.next(),
+                gas_used: self.u256(),
+                gas_limit: self.u256(),
+                timestamp: self.next(),
+                extra_data: self.bytes(),
+                ..Header::default()
+            };
+            if sealed {
+                header.set_seal(self.h256(), self.next());
+            }
+            let forks = self.below(4);
+            if forks > 0 {
+                let cancun = if forks > 2 {
+                    Some(CancunFields {
+                        blob_gas_used: self.u256(),
+                        excess_blob_gas: self.u256(),
+                        parent_beacon_block_root: self.h256(),
+                    })
+                } else {
+                    None
+                };
+                let shanghai = if forks > 1 {
+                    Some(ShanghaiFields {
+                        withdrawals_root: self.h256(),
+                        cancun,
+                    })
+                } else {
+                    None
+                };
+                header.london = Some(LondonFields {
+                    base_fee_per_gas: self.u256(),
+                    shanghai,
+                });
+            }
+            header
+        }
+
+        fn transaction(&mut self) -> TypedTransaction {
+            let access_list = (0..self.below(3))
+                .map(|_| AccessListItem {
+                    address: Address::from(self.h256()),
+                    storage_keys: (0..self.below(3)).map(|_| self.h256()).collect(),
+                })
+                .collect();
+            match self.below(3) {
+                0 => Transaction {
+                    nonce: self.next(),
+                    gas_price: self.u256(),
+                    gas_limit: self.u256(),
+                    recipient: self.recipient(),
+                    amount: self.u256(),
+                    payload: self.bytes(),
+                    v: self.u256(),
+                    r: self.u256(),
+                    s: self.u256(),
+                }.into(),
+                1 => AccessListTransaction {
+                    chain_id: self.next(),
+                    nonce: self.next(),
+                    gas_price: self.u256(),
+                    gas_limit: self.u256(),
+                    recipient: self.recipient(),
+                    amount: self.u256(),
+                    payload: self.bytes(),
+                    access_list,
+                    y_parity: self.below(2) as u8,
+                    r: self.u256(),
+                    s: self.u256(),
+                }.into(),
+                _ => DynamicFeeTransaction {
+                    chain_id: self.next(),
+                    nonce: self.next(),
+                    max_priority_fee_per_gas: self.u256(),
+                    max_fee_per_gas: self.u256(),
+                    gas_limit: self.u256(),
+                    recipient: self.recipient(),
+                    amount: self.u256(),
+                    payload: self.bytes(),
+                    access_list,
+                    y_parity: self.below(2) as u8,
+                    r: self.u256(),
+                    s: self.u256(),
+                }.into(),
+            }
+        }
+
+        fn block(&mut self, sealed: bool) -> Block {
+            Block {
+                header: self.header(sealed),
+                transactions: (0..self.below(4)).map(|_| self.transaction().into()).collect(),
+                uncles: (0..self.below(3)).map(|_| self.header(true)).collect(),
+            }
+        }
+    }
 
     #[test]
     fn test_block_decode() {
-        let block_rlp = "f9023ef901d8a083cafc574e1f51ba9dc0568fc617a08ea2429fb384059c972f13b19fa1c8dd55948888f1f195afa192cfee860698584c030f4c9db1a0ef1552a40b7165c3cd773806b9e0c165b75356e0314bf0706f279c729f51e017a05fe50b260da6308036625b850b5d6ced6d0a9f814c0688bc91ffb7b7a3a54b67a0bc37d79753ad738a6dac4921e57392f145d8887476de3f783dfa7edae9283e52b90100000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000008302000001832fefd8825208845506eb0780a0bd4472abb6659ebe3ee06ee4d7b72a00a9f4d001caca51342001075469aff49888a13a5a8c8f2bb1c4f861f85f800a82c35094095e7baea6a6c7c4c2dfeb977efac326af552d870a801ba09bea4c4daac7c7c52e093e6a4c35dbbcf8856f1af7b059ba20253e70848d094fa08a8fae537ce25ed8cb5af9adac3f141af69bd515bd2ba031522df09b97dd72b1".from_hex().unwrap();
+        let block_rlp = "f90260f901f9a083cafc574e1f51ba9dc0568fc617a08ea2429fb384059c972f13b19fa1c8dd55a01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347948888f1f195afa192cfee860698584c030f4c9db1a0ef1552a40b7165c3cd773806b9e0c165b75356e0314bf0706f279c729f51e017a05fe50b260da6308036625b850b5d6ced6d0a9f814c0688bc91ffb7b7a3a54b67a0bc37d79753ad738a6dac4921e57392f145d8887476de3f783dfa7edae9283e52b90100000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000008302000001832fefd8825208845506eb0780a0bd4472abb6659ebe3ee06ee4d7b72a00a9f4d001caca51342001075469aff49888a13a5a8c8f2bb1c4f861f85f800a82c35094095e7baea6a6c7c4c2dfeb977efac326af552d870a801ba09bea4c4daac7c7c52e093e6a4c35dbbcf8856f1af7b059ba20253e70848d094fa08a8fae537ce25ed8cb5af9adac3f141af69bd515bd2ba031522df09b97dd72b1c0".from_hex().unwrap();
         let block: Block = rlp::decode(&block_rlp);
         assert_eq!(block.transactions.len(), 1);
+        assert!(block.uncles.is_empty());
+        assert_eq!(block.compute_uncles_hash(), block.header.uncles_hash);
+        assert_eq!(block.compute_transactions_root(), block.header.transactions_root);
+        assert_eq!(
+            block.header.transactions_root,
+            "5fe50b260da6308036625b850b5d6ced6d0a9f814c0688bc91ffb7b7a3a54b67".into()
+        );
+    }
+
+    #[test]
+    fn test_block_with_uncles() {
+        let mut block = Block::default();
+        block.header.nonce = vec![0; 8];
+        let mut uncle = block.header.clone();
+        uncle.number = 7;
+        block.uncles = vec![uncle];
+        block.header.uncles_hash = block.compute_uncles_hash();
+        assert!(block.header.uncles_hash != Block::default().compute_uncles_hash());
+
+        let decoded: Block = rlp::decode(&rlp::encode(&block));
+        assert_eq!(decoded, block);
+        assert_eq!(decoded.uncles[0].number, 7);
+    }
+
+    #[test]
+    fn test_block_with_typed_transactions() {
+        let mut block = Block::default();
+        block.header.nonce = vec![0; 8];
+        block.transactions = vec![
+            Transaction::new().into(),
+            TypedTransaction::from(AccessListTransaction::default()).into(),
+            TypedTransaction::from(DynamicFeeTransaction::default()).into(),
+        ];
+        let decoded: Block = rlp::decode(&rlp::encode(&block));
+        assert_eq!(decoded, block);
+        assert_eq!(decoded.transactions[2].tx_type(), 2);
+    }
+
+    #[test]
+    fn test_encoding_round_trip() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..200 {
+            let sealed = rng.block(true);
+            let encoded = rlp::encode(&sealed).into_vec();
+            assert_eq!(sealed.rlp_bytes(true), encoded);
+            assert_eq!(rlp::decode::<Block>(&encoded), sealed);
+
+            assert_eq!(Block::decode_rlp(&UntrustedRlp::new(&encoded), true), Ok(sealed));
+
+            let unsealed = rng.block(false);
+            let bare = unsealed.rlp_bytes(false);
+            assert_eq!(Block::decode_rlp(&UntrustedRlp::new(&bare), false), Ok(unsealed.clone()));
+            assert_eq!(rlp::decode::<Block>(&unsealed.rlp_bytes(true)), unsealed);
+        }
+    }
+
+    #[test]
+    fn test_bare_fork_block_needs_seal_flag() {
+        let mut block = Block::default();
+        block.header.london = Some(LondonFields {
+            base_fee_per_gas: U256::from(7),
+            shanghai: Some(ShanghaiFields {
+                withdrawals_root: H256::from(1),
+                cancun: None,
+            }),
+        });
+        let bare = block.rlp_bytes(false);
+
+        // fifteen header items read as a sealed header, the fork fields
+        // land in the seal and fail to decode
+        assert_eq!(
+            UntrustedRlp::new(&bare).as_val::<Block>(),
+            Err(DecoderError::RlpIsTooShort)
+        );
+
+        assert_eq!(Block::decode_rlp(&UntrustedRlp::new(&bare), false), Ok(block));
+    }
+
+    #[test]
+    fn test_mismatched_encoding() {
+        let block = Block::default();
+        let mut s = RlpStream::new_list(2);
+        s.append(&block.header);
+        s.append_list(&block.transactions);
+        let legacy = s.out();
+        assert_eq!(
+            Block::decode_rlp(&UntrustedRlp::new(&legacy), true),
+            Err(BlockDecodeError::MismatchedLayout { items: 2 })
+        );
+        assert_eq!(
+            UntrustedRlp::new(&legacy).as_val::<Block>(),
+            Err(DecoderError::RlpIncorrectListLen)
+        );
     }
 }
@@ -0,0 +1,545 @@
+//! Persistent block store: headers and bodies by hash, the canonical chain
+//! by number, total difficulty and transaction locations. The heaviest
+//! chain by total difficulty is canonical.
+
+use std::sync::Arc;
+use ethereum_types::{H256, U256};
+use kvdb::{DBTransaction, KeyValueDB};
+use rlp::{self, Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
+use block::Block;
+use error::ChainError;
+use header::Header;
+use transaction::UnverifiedTransaction;
+
+pub const COL_HEADERS: u32 = 0;
+pub const COL_BODIES: u32 = 1;
+/// Block details, the canonical index, transaction locations and the best
+/// block.
+pub const COL_EXTRA: u32 = 2;
+/// Trie nodes, for a `JournalDB` sharing the database.
+pub const COL_STATE: u32 = 3;
+/// The journal of the nodes in `COL_STATE`.
+pub const COL_JOURNAL: u32 = 4;
+pub const NUM_COLUMNS: u32 = 5;
+
+const BEST_BLOCK_KEY: &[u8] = b"best";
+
+fn details_key(hash: &H256) -> Vec<u8> {
+    let mut key = b"d".to_vec();
+    key.extend_from_slice(hash);
+    key
+}
+
+fn canonical_key(number: u64) -> Vec<u8> {
+    let mut key = b"n".to_vec();
+    key.extend_from_slice(&number.to_be_bytes());
+    key
+}
+
+fn transaction_key(hash: &H256) -> Vec<u8> {
+    let mut key = b"t".to_vec();
+    key.extend_from_slice(hash);
+    key
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockDetails {
+    pub number: u64,
+    pub total_difficulty: U256,
+    pub parent_hash: H256,
+}
+
+impl Encodable for BlockDetails {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3);
+        s.append(&self.number);
+        s.append(&self.total_difficulty);
+        s.append(&self.parent_hash);
+    }
+}
+
+impl Decodable for BlockDetails {
+    fn decode(rlp: &UntrustedRlp) -> Result<Self, DecoderError> {
+        Ok(BlockDetails {
+            number: rlp.val_at(0)?,
+            total_difficulty: rlp.val_at(1)?,
+            parent_hash: rlp.val_at(2)?,
+        })
+    }
+}
+
+/// Where a canonical transaction was included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionAddress {
+    pub block_hash: H256,
+    pub index: usize,
+}
+
+impl Encodable for TransactionAddress {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2);
+        s.append(&self.block_hash);
+        s.append(&self.index);
+    }
+}
+
+impl Decodable for TransactionAddress {
+    fn decode(rlp: &UntrustedRlp) -> Result<Self, DecoderError> {
+        Ok(TransactionAddress {
+            block_hash: rlp.val_at(0)?,
+            index: rlp.val_at(1)?,
+        })
+    }
+}
+
+/// Path between two blocks through their common ancestor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeRoute {
+    /// From the first block back to the ancestor, exclusive.
+    pub retracted: Vec<H256>,
+    /// From the ancestor, exclusive, up to the second block.
+    pub enacted: Vec<H256>,
+    pub ancestor: H256,
+}
+
+/// How importing a block changed the canonical chain. A block that doesn't
+/// make its chain the heaviest is stored but only listed as omitted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportRoute {
+    pub retracted: Vec<H256>,
+    pub enacted: Vec<H256>,
+    pub omitted: Vec<H256>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BestBlock {
+    pub hash: H256,
+    pub number: u64,
+    pub total_difficulty: U256,
+}
+
+pub struct BlockChain {
+    db: Arc<dyn KeyValueDB>,
+    genesis_hash: H256,
+    best: BestBlock,
+}
+
+fn decode<T: Decodable>(bytes: &[u8]) -> Result<T, ChainError> {
+    Ok(UntrustedRlp::new(bytes).as_val()?)
+}
+
+fn decode_hash(bytes: &[u8]) -> Result<H256, ChainError> {
+    if bytes.len() != 32 {
+        return Err(ChainError::Corrupt(DecoderError::RlpInvalidLength));
+    }
+    Ok(H256::from(bytes))
+}
+
+impl BlockChain {
+    /// Opens the chain stored in `db`, starting it from `genesis` when the
+    /// database is empty.
+    pub fn new(db: Arc<dyn KeyValueDB>, genesis: &Block) -> Result<Self, ChainError> {
+        let genesis_hash = genesis.header.hash();
+        let best_hash = match db.get(COL_EXTRA, BEST_BLOCK_KEY)? {
+            Some(hash) => {
+                let stored = match db.get(COL_EXTRA, &canonical_key(0))? {
+                    Some(stored) => Some(decode_hash(&stored)?),
+                    None => None,
+                };
+                if stored != Some(genesis_hash) {
+                    return Err(ChainError::GenesisMismatch {
+                        expected: genesis_hash,
+                        got: stored.unwrap_or_default(),
+                    });
+                }
+                decode_hash(&hash)?
+            }
+            None => {
+                let details = BlockDetails {
+                    number: genesis.header.number,
+                    total_difficulty: genesis.header.difficulty,
+                    parent_hash: H256::zero(),
+                };
+                let mut batch = DBTransaction::new();
+                Self::write_block(&mut batch, genesis, &details);
+                Self::enact(&mut batch, genesis_hash, genesis);
+                batch.put(COL_EXTRA, BEST_BLOCK_KEY, &genesis_hash);
+                db.write(batch)?;
+                genesis_hash
+            }
+        };
+
+        let mut chain = BlockChain {
+            db,
+            genesis_hash,
+            best: BestBlock {
+                hash: best_hash,
+                number: 0,
+                total_difficulty: U256::zero(),
+            },
+        };
+        let details = chain.block_details(&best_hash)?.ok_or(ChainError::UnknownParent(best_hash))?;
+        chain.best.number = details.number;
+        chain.best.total_difficulty = details.total_difficulty;
+        Ok(chain)
+    }
+
+    fn write_block(batch: &mut DBTransaction, block: &Block, details: &BlockDetails) {
+        let hash = block.header.hash();
+        let mut body = RlpStream::new_list(2);
+        body.append_list(&block.transactions);
+        body.append_list(&block.uncles);
+        batch.put(COL_HEADERS, &hash, &rlp::encode(&block.header));
+        batch.put(COL_BODIES, &hash, &body.out());
+        batch.put(COL_EXTRA, &details_key(&hash), &rlp::encode(details));
+    }
+
+    /// Makes `block` the canonical block at its number.
+    fn enact(batch: &mut DBTransaction, hash: H256, block: &Block) {
+        batch.put(COL_EXTRA, &canonical_key(block.header.number), &hash);
+        for (index, tx) in block.transactions.iter().enumerate() {
+            let address = TransactionAddress {
+                block_hash: hash,
+                index,
+            };
+            batch.put(COL_EXTRA, &transaction_key(&tx.hash()), &rlp::encode(&address));
+        }
+    }
+
+    pub fn genesis_hash(&self) -> H256 {
+        self.genesis_hash
+    }
+
+    pub fn best_block(&self) -> &BestBlock {
+        &self.best
+    }
+
+    pub fn best_block_header(&self) -> Result<Header, ChainError> {
+        let hash = self.best.hash;
+        self.block_header(&hash)?.ok_or(ChainError::UnknownParent(hash))
+    }
+
+    pub fn is_known(&self, hash: &H256) -> Result<bool, ChainError> {
+        Ok(self.db.get(COL_EXTRA, &details_key(hash))?.is_some())
+    }
+
+    pub fn block_header(&self, hash: &H256) -> Result<Option<Header>, ChainError> {
+        match self.db.get(COL_HEADERS, hash)? {
+            Some(bytes) => decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn block(&self, hash: &H256) -> Result<Option<Block>, ChainError> {
+        let header = match self.block_header(hash)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let body = self.db.get(COL_BODIES, hash)?.ok_or(ChainError::MissingBody(*hash))?;
+        let body = UntrustedRlp::new(&body);
+        Ok(Some(Block {
+            header,
+            transactions: body.list_at(0)?,
+            uncles: body.list_at(1)?,
+        }))
+    }
+
+    pub fn block_details(&self, hash: &H256) -> Result<Option<BlockDetails>, ChainError> {
+        match self.db.get(COL_EXTRA, &details_key(hash))? {
+            Some(bytes) => decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Hash of the canonical block at `number`.
+    pub fn block_hash(&self, number: u64) -> Result<Option<H256>, ChainError> {
+        match self.db.get(COL_EXTRA, &canonical_key(number))? {
+            Some(hash) => decode_hash(&hash).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Location of a transaction in the canonical chain.
+    pub fn transaction_address(&self, hash: &H256) -> Result<Option<TransactionAddress>, ChainError> {
+        match self.db.get(COL_EXTRA, &transaction_key(hash))? {
+            Some(bytes) => decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn transaction(&self, hash: &H256) -> Result<Option<UnverifiedTransaction>, ChainError> {
+        let address = match self.transaction_address(hash)? {
+            Some(address) => address,
+            None => return Ok(None),
+        };
+        Ok(self.block(&address.block_hash)?
+            .and_then(|block| block.transactions.into_iter().nth(address.index)))
+    }
+
+    fn known_details(&self, hash: &H256) -> Result<BlockDetails, ChainError> {
+        self.block_details(hash)?.ok_or(ChainError::UnknownParent(*hash))
+    }
+
+    /// Route from block `from` to block `to`, both must be known.
+    pub fn tree_route(&self, from: H256, to: H256) -> Result<TreeRoute, ChainError> {
+        let mut retracted = vec![];
+        let mut enacted = vec![];
+        let (mut from, mut to) = (from, to);
+        let mut from_details = self.known_details(&from)?;
+        let mut to_details = self.known_details(&to)?;
+
+        while from_details.number > to_details.number {
+            retracted.push(from);
+            from = from_details.parent_hash;
+            from_details = self.known_details(&from)?;
+        }
+        while to_details.number > from_details.number {
+            enacted.push(to);
+            to = to_details.parent_hash;
+            to_details = self.known_details(&to)?;
+        }
+        while from != to {
+            retracted.push(from);
+            enacted.push(to);
+            from = from_details.parent_hash;
+            to = to_details.parent_hash;
+            from_details = self.known_details(&from)?;
+            to_details = self.known_details(&to)?;
+        }
+
+        enacted.reverse();
+        Ok(TreeRoute {
+            retracted,
+            enacted,
+            ancestor: from,
+        })
+    }
+
+    /// Stores `block`, whose parent must be known, and makes it the best
+    /// block when its chain has the most total difficulty.
+    pub fn insert_block(&mut self, block: &Block) -> Result<ImportRoute, ChainError> {
+        let hash = block.header.hash();
+        if self.is_known(&hash)? {
+            return Err(ChainError::AlreadyInChain(hash));
+        }
+        let parent = self.block_details(&block.header.parent_hash)?
+            .ok_or(ChainError::UnknownParent(block.header.parent_hash))?;
+        if parent.number.checked_add(1) != Some(block.header.number) {
+            return Err(ChainError::InvalidNumber {
+                parent: parent.number,
+                got: block.header.number,
+            });
+        }
+        let (total_difficulty, overflow) = parent.total_difficulty.overflowing_add(block.header.difficulty);
+        if overflow {
+            return Err(ChainError::TotalDifficultyOverflow(hash));
+        }
+        let details = BlockDetails {
+            number: block.header.number,
+            total_difficulty,
+            parent_hash: block.header.parent_hash,
+        };
+
+        let mut batch = DBTransaction::new();
+        Self::write_block(&mut batch, block, &details);
+        if details.total_difficulty <= self.best.total_difficulty {
+            self.db.write(batch)?;
+            return Ok(ImportRoute {
+                omitted: vec![hash],
+                ..ImportRoute::default()
+            });
+        }
+
+        let route = self.tree_route(self.best.hash, block.header.parent_hash)?;
+        for retracted in &route.retracted {
+            let old = self.block(retracted)?.ok_or(ChainError::UnknownParent(*retracted))?;
+            for tx in &old.transactions {
+                batch.delete(COL_EXTRA, &transaction_key(&tx.hash()));
+            }
+            if old.header.number > details.number {
+                batch.delete(COL_EXTRA, &canonical_key(old.header.number));
+            }
+        }
+        // enacted after the deletes, a transaction in both chains moves
+        for enacted in &route.enacted {
+            let new = self.block(enacted)?.ok_or(ChainError::UnknownParent(*enacted))?;
+            Self::enact(&mut batch, *enacted, &new);
+        }
+        Self::enact(&mut batch, hash, block);
+        batch.put(COL_EXTRA, BEST_BLOCK_KEY, &hash);
+        self.db.write(batch)?;
+
+        self.best = BestBlock {
+            hash,
+            number: details.number,
+            total_difficulty: details.total_difficulty,
+        };
+        let mut enacted = route.enacted;
+        enacted.push(hash);
+        Ok(ImportRoute {
+            retracted: route.retracted,
+            enacted,
+            omitted: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use ethereum_types::{H256, U256};
+    use kvdb::{DBTransaction, InMemory, KeyValueDB};
+    use block::Block;
+    use error::ChainError;
+    use header::Header;
+    use transaction::Transaction;
+    use super::{canonical_key, BlockChain, ImportRoute, TransactionAddress, COL_BODIES, COL_EXTRA, NUM_COLUMNS};
+
+    fn genesis() -> Block {
+        Block {
+            header: Header {
+                difficulty: U256::from(10),
+                ..Header::default()
+            },
+            ..Block::default()
+        }
+    }
+
+    /// A child of `parent`, `fork` tells siblings apart and `nonces` picks
+    /// the transactions it includes.
+    fn child(parent: &Block, difficulty: u64, fork: u8, nonces: &[u64]) -> Block {
+        Block {
+            header: Header {
+                parent_hash: parent.header.hash(),
+                number: parent.header.number + 1,
+                difficulty: U256::from(difficulty),
+                extra_data: vec![fork],
+                ..Header::default()
+            },
+            transactions: nonces
+                .iter()
+                .map(|&nonce| Transaction { nonce, ..Transaction::new() }.into())
+                .collect(),
+            uncles: vec![],
+        }
+    }
+
+    fn tx_hash(nonce: u64) -> H256 {
+        Transaction { nonce, ..Transaction::new() }.hash()
+    }
+
+    #[test]
+    fn test_linear_chain() {
+        let db = Arc::new(InMemory::new(NUM_COLUMNS));
+        let genesis = genesis();
+        let block1 = child(&genesis, 10, 0, &[0, 1]);
+        let block2 = child(&block1, 10, 0, &[2]);
+        {
+            let mut chain = BlockChain::new(db.clone(), &genesis).unwrap();
+            assert_eq!(chain.insert_block(&block1).unwrap().enacted, vec![block1.header.hash()]);
+            chain.insert_block(&block2).unwrap();
+            assert!(matches!(chain.insert_block(&block2), Err(ChainError::AlreadyInChain(_))));
+            let orphan = child(&child(&block2, 10, 0, &[]), 10, 0, &[]);
+            assert!(matches!(chain.insert_block(&orphan), Err(ChainError::UnknownParent(_))));
+            let mut skipping = child(&block2, 10, 0, &[]);
+            skipping.header.number = 4;
+            assert!(matches!(
+                chain.insert_block(&skipping),
+                Err(ChainError::InvalidNumber { parent: 2, got: 4 })
+            ));
+            let mut heavy = child(&block2, 10, 0, &[]);
+            heavy.header.difficulty = U256::max_value();
+            assert!(matches!(chain.insert_block(&heavy), Err(ChainError::TotalDifficultyOverflow(_))));
+            assert!(!chain.is_known(&heavy.header.hash()).unwrap());
+        }
+
+        let chain = BlockChain::new(db.clone(), &genesis).unwrap();
+        assert_eq!(chain.best_block().hash, block2.header.hash());
+        assert_eq!(chain.best_block().number, 2);
+        assert_eq!(chain.best_block().total_difficulty, U256::from(30));
+        assert_eq!(chain.block_hash(1).unwrap(), Some(block1.header.hash()));
+        assert_eq!(chain.block(&block1.header.hash()).unwrap(), Some(block1.clone()));
+        assert_eq!(
+            chain.transaction_address(&tx_hash(1)).unwrap(),
+            Some(TransactionAddress {
+                block_hash: block1.header.hash(),
+                index: 1,
+            })
+        );
+        assert_eq!(chain.transaction(&tx_hash(2)).unwrap(), Some(block2.transactions[0].clone()));
+
+        let other_genesis = child(&genesis, 1, 0, &[]);
+        assert!(matches!(
+            BlockChain::new(db, &other_genesis),
+            Err(ChainError::GenesisMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_reorganisation() {
+        let genesis = genesis();
+        let mut chain = BlockChain::new(Arc::new(InMemory::new(NUM_COLUMNS)), &genesis).unwrap();
+        let a1 = child(&genesis, 10, 0, &[0]);
+        let a2 = child(&a1, 10, 0, &[1]);
+        let a3 = child(&a2, 10, 0, &[2]);
+        let b2 = child(&a1, 15, 1, &[1]);
+        let b3 = child(&b2, 10, 1, &[3]);
+        for block in &[&a1, &a2, &a3] {
+            chain.insert_block(block).unwrap();
+        }
+
+        // b2 is lighter than a3, b3 is heavier
+        assert_eq!(
+            chain.insert_block(&b2).unwrap(),
+            ImportRoute {
+                omitted: vec![b2.header.hash()],
+                ..ImportRoute::default()
+            }
+        );
+        assert_eq!(chain.best_block().hash, a3.header.hash());
+        assert_eq!(
+            chain.insert_block(&b3).unwrap(),
+            ImportRoute {
+                retracted: vec![a3.header.hash(), a2.header.hash()],
+                enacted: vec![b2.header.hash(), b3.header.hash()],
+                omitted: vec![],
+            }
+        );
+
+        assert_eq!(chain.best_block().hash, b3.header.hash());
+        assert_eq!(chain.block_hash(2).unwrap(), Some(b2.header.hash()));
+        assert_eq!(chain.block_hash(3).unwrap(), Some(b3.header.hash()));
+        assert_eq!(chain.transaction_address(&tx_hash(1)).unwrap().unwrap().block_hash, b2.header.hash());
+        assert_eq!(chain.transaction_address(&tx_hash(2)).unwrap(), None);
+        assert_eq!(chain.transaction_address(&tx_hash(0)).unwrap().unwrap().block_hash, a1.header.hash());
+
+        // back onto a shorter but heavier chain, dropping block 3
+        let c2 = child(&a1, 100, 2, &[]);
+        let route = chain.insert_block(&c2).unwrap();
+        assert_eq!(route.retracted, vec![b3.header.hash(), b2.header.hash()]);
+        assert_eq!(chain.block_hash(3).unwrap(), None);
+        assert_eq!(chain.transaction_address(&tx_hash(3)).unwrap(), None);
+        assert_eq!(chain.tree_route(b3.header.hash(), a3.header.hash()).unwrap().ancestor, a1.header.hash());
+    }
+
+    #[test]
+    fn test_corrupt_chain_data() {
+        let db = Arc::new(InMemory::new(NUM_COLUMNS));
+        let genesis = genesis();
+        let block1 = child(&genesis, 10, 0, &[]);
+        let mut chain = BlockChain::new(db.clone(), &genesis).unwrap();
+        chain.insert_block(&block1).unwrap();
+
+        let mut batch = DBTransaction::new();
+        batch.put(COL_EXTRA, &canonical_key(1), &[1, 2, 3]);
+        batch.delete(COL_BODIES, &block1.header.hash());
+        db.write(batch).unwrap();
+        assert!(matches!(chain.block_hash(1), Err(ChainError::Corrupt(_))));
+        assert!(matches!(chain.block(&block1.header.hash()), Err(ChainError::MissingBody(_))));
+
+        let mut batch = DBTransaction::new();
+        batch.put(COL_EXTRA, &canonical_key(0), &[]);
+        db.write(batch).unwrap();
+        assert!(matches!(BlockChain::new(db, &genesis), Err(ChainError::Corrupt(_))));
+    }
+}
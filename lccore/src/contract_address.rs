@@ -0,0 +1,62 @@
+use ethereum_types::{Address, H256};
+use keccak_hash::keccak;
+use rlp::RlpStream;
+
+/// Address of a contract created by `sender` with a creation transaction
+/// (or the CREATE opcode): `keccak(rlp([sender, nonce]))[12..]`.
+pub fn contract_address(sender: &Address, nonce: u64) -> Address {
+    let mut s = RlpStream::new_list(2);
+    s.append(sender);
+    s.append(&nonce);
+    Address::from(keccak(s.as_raw()))
+}
+
+/// Address of a contract created with CREATE2 (EIP-1014):
+/// `keccak(0xff ++ sender ++ salt ++ keccak(init_code))[12..]`.
+pub fn create2_address(sender: &Address, salt: &H256, init_code: &[u8]) -> Address {
+    let mut buffer = [0u8; 1 + 20 + 32 + 32];
+    buffer[0] = 0xff;
+    buffer[1..21].copy_from_slice(&sender[..]);
+    buffer[21..53].copy_from_slice(&salt[..]);
+    buffer[53..85].copy_from_slice(&keccak(init_code)[..]);
+    Address::from(keccak(&buffer[..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use ethereum_types::{Address, H256};
+    use super::{contract_address, create2_address};
+
+    #[test]
+    fn test_contract_address() {
+        let sender: Address = "6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0".into();
+        assert_eq!(
+            contract_address(&sender, 0),
+            "cd234a471b72ba2f1ccf0a70fcaba648a5eecd8d".into()
+        );
+        assert_eq!(
+            contract_address(&sender, 1),
+            "343c43a37d37dff08ae8c4a11544c718abb4fcf8".into()
+        );
+        assert_eq!(
+            contract_address(&sender, 2),
+            "f778b86fa74e846c4f0a1fbd1335fe81c00a0c91".into()
+        );
+    }
+
+    #[test]
+    fn test_create2_address() {
+        assert_eq!(
+            create2_address(&Address::default(), &H256::default(), &[0x00]),
+            "4d1a2e2bb4f88f0250f26ffff098b0b30b26bf38".into()
+        );
+        assert_eq!(
+            create2_address(
+                &"deadbeef00000000000000000000000000000000".into(),
+                &H256::default(),
+                &[0x00]
+            ),
+            "b928f69bb1d91cd65274e3c79d8986362984fda3".into()
+        );
+    }
+}
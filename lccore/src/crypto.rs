@@ -0,0 +1,115 @@
+use std::fmt;
+use ethereum_types::{Address, H256, H512, U256};
+use keccak_hash::keccak;
+use secp256k1::{self, Message, PublicKey, Secp256k1, SecretKey};
+use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+
+pub type Secret = H256;
+pub type Public = H512;
+
+lazy_static! {
+    /// The secp256k1 curve order.
+    pub static ref CURVE_ORDER: U256 =
+        "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141".into();
+    static ref HALF_CURVE_ORDER: U256 = *CURVE_ORDER / U256::from(2);
+    static ref SECP256K1: Secp256k1<secp256k1::All> = Secp256k1::new();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    InvalidSecret,
+    InvalidSignature,
+    InvalidMessage,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match *self {
+            Error::InvalidSecret => "invalid secret key",
+            Error::InvalidSignature => "invalid signature",
+            Error::InvalidMessage => "invalid message",
+        };
+        f.write_str(msg)
+    }
+}
+
+/// Recoverable ECDSA signature, `v` is the recovery id (0 or 1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub r: H256,
+    pub s: H256,
+    pub v: u8,
+}
+
+/// Whether `r` and `s` are in `[1, n)`.
+pub fn is_valid_signature_values(r: &U256, s: &U256) -> bool {
+    !r.is_zero() && !s.is_zero() && *r < *CURVE_ORDER && *s < *CURVE_ORDER
+}
+
+/// Whether `s` is in the lower half of the curve order (EIP-2), which
+/// removes the malleable second form of every signature.
+pub fn is_low_s(s: &U256) -> bool {
+    *s <= *HALF_CURVE_ORDER
+}
+
+pub fn sign(secret: &Secret, message: &H256) -> Result<Signature, Error> {
+    let key = SecretKey::from_slice(&secret[..]).map_err(|_| Error::InvalidSecret)?;
+    let message = Message::from_slice(&message[..]).map_err(|_| Error::InvalidMessage)?;
+    let (recovery_id, data) = SECP256K1
+        .sign_recoverable(&message, &key)
+        .serialize_compact();
+    Ok(Signature {
+        r: H256::from(&data[0..32]),
+        s: H256::from(&data[32..64]),
+        v: recovery_id.to_i32() as u8,
+    })
+}
+
+pub fn recover(signature: &Signature, message: &H256) -> Result<Public, Error> {
+    let recovery_id =
+        RecoveryId::from_i32(i32::from(signature.v)).map_err(|_| Error::InvalidSignature)?;
+    let mut data = [0u8; 64];
+    data[0..32].copy_from_slice(&signature.r[..]);
+    data[32..64].copy_from_slice(&signature.s[..]);
+    let signature =
+        RecoverableSignature::from_compact(&data, recovery_id).map_err(|_| Error::InvalidSignature)?;
+    let message = Message::from_slice(&message[..]).map_err(|_| Error::InvalidMessage)?;
+    let public = SECP256K1
+        .recover(&message, &signature)
+        .map_err(|_| Error::InvalidSignature)?;
+    Ok(serialize_public(&public))
+}
+
+pub fn secret_to_public(secret: &Secret) -> Result<Public, Error> {
+    let key = SecretKey::from_slice(&secret[..]).map_err(|_| Error::InvalidSecret)?;
+    Ok(serialize_public(&PublicKey::from_secret_key(&SECP256K1, &key)))
+}
+
+pub fn public_to_address(public: &Public) -> Address {
+    Address::from(keccak(public))
+}
+
+fn serialize_public(public: &PublicKey) -> Public {
+    // drop the 0x04 uncompressed key prefix
+    Public::from(&public.serialize_uncompressed()[1..])
+}
+
+#[cfg(test)]
+mod tests {
+    use keccak_hash::keccak;
+    use super::{public_to_address, recover, secret_to_public, sign, Secret};
+
+    #[test]
+    fn test_sign_and_recover() {
+        let secret: Secret = "4646464646464646464646464646464646464646464646464646464646464646".into();
+        let public = secret_to_public(&secret).unwrap();
+        assert_eq!(
+            public_to_address(&public),
+            "9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f".into()
+        );
+
+        let message = keccak(b"linkchain");
+        let signature = sign(&secret, &message).unwrap();
+        assert_eq!(recover(&signature, &message).unwrap(), public);
+    }
+}
@@ -0,0 +1,229 @@
+//! Expected block difficulty under the Frontier, Homestead (EIP-2) and
+//! Byzantium (EIP-100) rules, including the exponential difficulty bomb.
+
+use std::cmp;
+use std::collections::BTreeMap;
+use ethereum_types::U256;
+use keccak_hash::KECCAK_EMPTY_LIST_RLP;
+use error::BlockError;
+use header::Header;
+
+const EXP_DIFF_PERIOD: u64 = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DifficultyParams {
+    pub minimum_difficulty: U256,
+    pub difficulty_bound_divisor: U256,
+    /// Frontier target block time, blocks faster than this raise difficulty.
+    pub duration_limit: u64,
+    pub homestead_transition: u64,
+    /// From here on the adjustment also accounts for parent uncles (EIP-100).
+    pub byzantium_transition: u64,
+    /// Block number from which each delay, in blocks, pushes back the bomb.
+    /// Delays are cumulative.
+    pub difficulty_bomb_delays: BTreeMap<u64, u64>,
+    /// The bomb is switched off from this block on.
+    pub bomb_defuse_transition: u64,
+}
+
+impl DifficultyParams {
+    /// Ethereum mainnet transitions up to Constantinople.
+    pub fn mainnet() -> Self {
+        let mut difficulty_bomb_delays = BTreeMap::new();
+        difficulty_bomb_delays.insert(4_370_000, 3_000_000);
+        difficulty_bomb_delays.insert(7_280_000, 2_000_000);
+        DifficultyParams {
+            minimum_difficulty: U256::from(131_072),
+            difficulty_bound_divisor: U256::from(2048),
+            duration_limit: 13,
+            homestead_transition: 1_150_000,
+            byzantium_transition: 4_370_000,
+            difficulty_bomb_delays,
+            bomb_defuse_transition: u64::MAX,
+        }
+    }
+
+    /// Difficulty pinned at one with no bomb, so any seal meets the boundary.
+    pub fn dev() -> Self {
+        DifficultyParams {
+            minimum_difficulty: U256::one(),
+            homestead_transition: 0,
+            byzantium_transition: 0,
+            difficulty_bomb_delays: BTreeMap::new(),
+            bomb_defuse_transition: 0,
+            ..DifficultyParams::mainnet()
+        }
+    }
+
+    /// Difficulty of a child of `parent` sealed at `timestamp`. Since
+    /// Byzantium the adjustment depends on whether the parent includes uncles.
+    pub fn calculate_difficulty(&self, parent: &Header, timestamp: u64) -> Result<U256, BlockError> {
+        let parent_has_uncles = parent.uncles_hash != KECCAK_EMPTY_LIST_RLP;
+        let number = parent.number.checked_add(1).ok_or(BlockError::NumberOverflow)?;
+        let adjustment = parent.difficulty / self.difficulty_bound_divisor;
+        let elapsed = timestamp.saturating_sub(parent.timestamp);
+
+        let mut target = if number < self.homestead_transition {
+            if elapsed >= self.duration_limit {
+                parent.difficulty - adjustment
+            } else {
+                parent.difficulty + adjustment
+            }
+        } else {
+            let threshold = if number >= self.byzantium_transition && parent_has_uncles {
+                2
+            } else {
+                1
+            };
+            let step = elapsed / if number < self.byzantium_transition { 10 } else { 9 };
+            if step <= threshold {
+                parent.difficulty + adjustment * U256::from(threshold - step)
+            } else {
+                let multiplier = cmp::min(step - threshold, 99);
+                parent
+                    .difficulty
+                    .saturating_sub(adjustment * U256::from(multiplier))
+            }
+        };
+        target = cmp::max(self.minimum_difficulty, target);
+
+        if number < self.bomb_defuse_transition {
+            let delay: u64 = self.difficulty_bomb_delays
+                .range(..=number)
+                .map(|(_, delay)| *delay)
+                .sum();
+            let period = number.saturating_sub(delay) / EXP_DIFF_PERIOD;
+            if period > 1 {
+                target = cmp::max(self.minimum_difficulty, target + (U256::one() << (period - 2) as usize));
+            }
+        }
+        Ok(target)
+    }
+
+    /// Checks `header.difficulty` against the value derived from its parent.
+    pub fn verify_difficulty(&self, header: &Header, parent: &Header) -> Result<(), BlockError> {
+        let expected = self.calculate_difficulty(parent, header.timestamp)?;
+        if header.difficulty != expected {
+            return Err(BlockError::InvalidDifficulty {
+                expected,
+                got: header.difficulty,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Default for DifficultyParams {
+    fn default() -> Self {
+        DifficultyParams::mainnet()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethereum_types::U256;
+    use error::BlockError;
+    use header::Header;
+    use super::DifficultyParams;
+
+    fn header_at(number: u64, difficulty: u64) -> Header {
+        Header {
+            number,
+            timestamp: 1_000_000,
+            difficulty: U256::from(difficulty),
+            ..Header::default()
+        }
+    }
+
+    #[test]
+    fn test_frontier_difficulty() {
+        let params = DifficultyParams::mainnet();
+        // mainnet block 1 was sealed long after genesis, so difficulty dropped
+        let genesis = Header {
+            difficulty: U256::from(17_179_869_184u64),
+            ..Header::default()
+        };
+        assert_eq!(
+            params.calculate_difficulty(&genesis, 1_438_269_988).unwrap(),
+            U256::from(17_171_480_576u64)
+        );
+
+        let parent = header_at(1000, 2_048_000);
+        assert_eq!(params.calculate_difficulty(&parent, 1_000_012).unwrap(), U256::from(2_049_000));
+        assert_eq!(params.calculate_difficulty(&parent, 1_000_013).unwrap(), U256::from(2_047_000));
+    }
+
+    #[test]
+    fn test_homestead_and_byzantium_difficulty() {
+        let mut params = DifficultyParams::mainnet();
+        params.difficulty_bomb_delays.clear();
+        params.bomb_defuse_transition = 0;
+
+        let parent = header_at(1_200_000, 2_048_000);
+        assert_eq!(params.calculate_difficulty(&parent, 1_000_005).unwrap(), U256::from(2_049_000));
+        assert_eq!(params.calculate_difficulty(&parent, 1_000_015).unwrap(), U256::from(2_048_000));
+        assert_eq!(params.calculate_difficulty(&parent, 1_000_035).unwrap(), U256::from(2_046_000));
+        // the downward adjustment is capped at 99 steps, the floor at the minimum
+        assert_eq!(
+            params.calculate_difficulty(&parent, 1_010_000).unwrap(),
+            U256::from(2_048_000 - 99 * 1000)
+        );
+        assert_eq!(
+            params.calculate_difficulty(&header_at(1_200_000, 135_000), 1_010_000).unwrap(),
+            params.minimum_difficulty
+        );
+
+        // Byzantium: 9 second steps, and uncles raise the target by one step
+        let parent = header_at(4_400_000, 2_048_000);
+        assert_eq!(params.calculate_difficulty(&parent, 1_000_009).unwrap(), U256::from(2_048_000));
+        let mut parent = parent;
+        parent.uncles_hash = 1.into();
+        assert_eq!(params.calculate_difficulty(&parent, 1_000_009).unwrap(), U256::from(2_049_000));
+    }
+
+    #[test]
+    fn test_difficulty_bomb() {
+        let params = DifficultyParams::mainnet();
+        let parent = header_at(199_999, 2_048_000);
+        // period 2 adds 2^0
+        assert_eq!(params.calculate_difficulty(&parent, 1_000_013).unwrap(), U256::from(2_047_001));
+
+        // Byzantium delays the bomb by 3M blocks: (4_400_000 - 3_000_000) / 100_000 = 14
+        let parent = header_at(4_399_999, 2_048_000);
+        assert_eq!(
+            params.calculate_difficulty(&parent, 1_000_009).unwrap(),
+            U256::from(2_048_000 + (1 << 12))
+        );
+
+        let mut defused = params.clone();
+        defused.bomb_defuse_transition = 0;
+        assert_eq!(defused.calculate_difficulty(&parent, 1_000_009).unwrap(), U256::from(2_048_000));
+    }
+
+    #[test]
+    fn test_verify_difficulty() {
+        let params = DifficultyParams::mainnet();
+        let parent = header_at(1000, 2_048_000);
+        let mut header = Header {
+            number: 1001,
+            timestamp: 1_000_020,
+            difficulty: U256::from(2_047_000),
+            ..Header::default()
+        };
+        assert_eq!(params.verify_difficulty(&header, &parent), Ok(()));
+        header.difficulty = U256::from(2_048_000);
+        assert_eq!(
+            params.verify_difficulty(&header, &parent),
+            Err(BlockError::InvalidDifficulty {
+                expected: U256::from(2_047_000),
+                got: U256::from(2_048_000),
+            })
+        );
+
+        let parent = header_at(u64::MAX, 2_048_000);
+        assert_eq!(
+            params.verify_difficulty(&header, &parent),
+            Err(BlockError::NumberOverflow)
+        );
+    }
+}
@@ -0,0 +1,745 @@
+//! Clique-style proof of authority (EIP-225). Authorized signers take turns
+//! sealing blocks, the seal is a signature at the end of `extra_data`, and
+//! signers are added or removed by majority vote through `coinbase`/`nonce`.
+
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::RwLock;
+use byteorder::{BigEndian, ByteOrder};
+use bytes::Bytes;
+use ethereum_types::{Address, H256, U256};
+use keccak_hash::KECCAK_EMPTY_LIST_RLP;
+use crypto::{self, Secret, Signature};
+use engines::{Engine, Seal};
+use error::BlockError;
+use header::Header;
+use verification::{self, SealVerifier, VerificationParams};
+
+/// Bytes reserved for signer vanity at the start of `extra_data`.
+pub const EXTRA_VANITY: usize = 32;
+/// Bytes reserved for the signer signature at the end of `extra_data`.
+pub const EXTRA_SEAL: usize = 65;
+const NONCE_AUTH: u64 = u64::MAX;
+const NONCE_DROP: u64 = 0;
+const DIFF_IN_TURN: u64 = 2;
+const DIFF_NO_TURN: u64 = 1;
+/// Snapshots of the most recent blocks kept in memory, older ones are
+/// rebuilt with `Clique::import_headers`.
+const IN_MEMORY_SNAPSHOTS: u64 = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CliqueParams {
+    /// Minimum seconds between blocks.
+    pub period: u64,
+    /// Blocks between checkpoints, which reset pending votes and list the
+    /// current signers in `extra_data`.
+    pub epoch: u64,
+}
+
+impl Default for CliqueParams {
+    fn default() -> Self {
+        CliqueParams {
+            period: 15,
+            epoch: 30_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Vote {
+    signer: Address,
+    candidate: Address,
+    authorize: bool,
+}
+
+/// Signer set and vote state after a given block.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    number: u64,
+    signers: BTreeSet<Address>,
+    /// Recent signers by block number, they may not sign again yet.
+    recents: BTreeMap<u64, Address>,
+    votes: Vec<Vote>,
+}
+
+impl Snapshot {
+    /// Checkpoint blocks list the signers, votes and recent signers start
+    /// over from them.
+    fn from_checkpoint(header: &Header) -> Result<Self, BlockError> {
+        Ok(Snapshot {
+            number: header.number,
+            signers: extra_data_signers(&header.extra_data)?.into_iter().collect(),
+            recents: BTreeMap::new(),
+            votes: vec![],
+        })
+    }
+
+    fn is_in_turn(&self, number: u64, signer: &Address) -> bool {
+        self.signers
+            .iter()
+            .position(|s| s == signer)
+            .is_some_and(|index| number % self.signers.len() as u64 == index as u64)
+    }
+
+    fn signer_limit(&self) -> u64 {
+        self.signers.len() as u64 / 2 + 1
+    }
+
+    fn recently_signed(&self, signer: &Address) -> bool {
+        self.recents.values().any(|s| s == signer)
+    }
+
+    fn is_valid_vote(&self, candidate: &Address, authorize: bool) -> bool {
+        self.signers.contains(candidate) != authorize
+    }
+
+    fn apply(&mut self, header: &Header, signer: Address, epoch: u64) -> Result<(), BlockError> {
+        let number = header.number;
+        if number.is_multiple_of(epoch) {
+            self.votes.clear();
+        }
+        if number >= self.signer_limit() {
+            self.recents.remove(&(number - self.signer_limit()));
+        }
+        if !self.signers.contains(&signer) {
+            return Err(BlockError::UnauthorizedSigner(signer));
+        }
+        if self.recently_signed(&signer) {
+            return Err(BlockError::RecentlySigned(signer));
+        }
+        self.recents.insert(number, signer);
+        self.number = number;
+
+        let candidate = header.coinbase;
+        let authorize = header.nonce() == NONCE_AUTH;
+        self.votes
+            .retain(|vote| vote.signer != signer || vote.candidate != candidate);
+        if !self.is_valid_vote(&candidate, authorize) {
+            return Ok(());
+        }
+        self.votes.push(Vote {
+            signer,
+            candidate,
+            authorize,
+        });
+
+        let tally = self.votes
+            .iter()
+            .filter(|vote| vote.candidate == candidate && vote.authorize == authorize)
+            .count();
+        if tally > self.signers.len() / 2 {
+            if authorize {
+                self.signers.insert(candidate);
+            } else {
+                self.signers.remove(&candidate);
+                if number >= self.signer_limit() {
+                    self.recents.remove(&(number - self.signer_limit()));
+                }
+                self.votes.retain(|vote| vote.signer != candidate);
+            }
+            self.votes.retain(|vote| vote.candidate != candidate);
+        }
+        Ok(())
+    }
+}
+
+pub struct Clique {
+    params: CliqueParams,
+    snapshots: RwLock<HashMap<H256, Snapshot>>,
+    signer: RwLock<Option<(Address, Secret)>>,
+    proposals: RwLock<BTreeMap<Address, bool>>,
+}
+
+impl Clique {
+    /// Starts from the signers listed in the genesis `extra_data`.
+    pub fn new(params: CliqueParams, genesis: &Header) -> Result<Self, BlockError> {
+        let mut snapshots = HashMap::new();
+        snapshots.insert(genesis.hash(), Snapshot::from_checkpoint(genesis)?);
+        Ok(Clique {
+            params,
+            snapshots: RwLock::new(snapshots),
+            signer: RwLock::new(None),
+            proposals: RwLock::new(BTreeMap::new()),
+        })
+    }
+
+    pub fn params(&self) -> &CliqueParams {
+        &self.params
+    }
+
+    /// Key this node seals with.
+    pub fn set_signer(&self, secret: Secret) -> Result<(), crypto::Error> {
+        let address = crypto::public_to_address(&crypto::secret_to_public(&secret)?);
+        *self.signer.write().expect("clique lock poisoned") = Some((address, secret));
+        Ok(())
+    }
+
+    /// Vote to add (`authorize`) or remove `candidate` in blocks we seal,
+    /// until the vote passes or is discarded.
+    pub fn propose(&self, candidate: Address, authorize: bool) {
+        self.proposals
+            .write()
+            .expect("clique lock poisoned")
+            .insert(candidate, authorize);
+    }
+
+    pub fn discard_proposal(&self, candidate: &Address) {
+        self.proposals
+            .write()
+            .expect("clique lock poisoned")
+            .remove(candidate);
+    }
+
+    /// Rebuilds the signer snapshot after the last of `headers`, such as
+    /// after a restart. `headers` are in chain order and start at a
+    /// checkpoint block or a block whose snapshot is still in memory.
+    pub fn import_headers(&self, headers: &[Header]) -> Result<(), BlockError> {
+        let (first, rest) = match headers.split_first() {
+            Some(split) => split,
+            None => return Ok(()),
+        };
+        let mut snapshot = match self.snapshot(&first.hash()) {
+            Ok(snapshot) => snapshot,
+            Err(_) if self.is_checkpoint(first.number) => Snapshot::from_checkpoint(first)?,
+            Err(e) => return Err(e),
+        };
+        let mut hash = first.hash();
+        for header in rest {
+            if header.parent_hash != hash {
+                return Err(BlockError::InvalidParentHash {
+                    expected: hash,
+                    got: header.parent_hash,
+                });
+            }
+            snapshot.apply(header, recover_signer(header)?, self.params.epoch)?;
+            hash = header.hash();
+        }
+        self.insert_snapshot(hash, snapshot);
+        Ok(())
+    }
+
+    /// Authorized signers after the block `hash`, if it has been verified.
+    pub fn signers(&self, hash: &H256) -> Option<Vec<Address>> {
+        self.snapshots
+            .read()
+            .expect("clique lock poisoned")
+            .get(hash)
+            .map(|snapshot| snapshot.signers.iter().cloned().collect())
+    }
+
+    fn snapshot(&self, hash: &H256) -> Result<Snapshot, BlockError> {
+        self.snapshots
+            .read()
+            .expect("clique lock poisoned")
+            .get(hash)
+            .cloned()
+            .ok_or(BlockError::UnknownParent(*hash))
+    }
+
+    fn insert_snapshot(&self, hash: H256, snapshot: Snapshot) {
+        let number = snapshot.number;
+        let mut snapshots = self.snapshots.write().expect("clique lock poisoned");
+        snapshots.insert(hash, snapshot);
+        snapshots.retain(|_, snapshot| snapshot.number + IN_MEMORY_SNAPSHOTS > number);
+    }
+
+    fn is_checkpoint(&self, number: u64) -> bool {
+        number.is_multiple_of(self.params.epoch)
+    }
+
+    /// Earliest timestamp of a child of `parent`.
+    fn min_timestamp(&self, parent: &Header) -> Result<u64, BlockError> {
+        parent.timestamp.checked_add(self.params.period).ok_or(BlockError::TimestampOverflow)
+    }
+}
+
+/// `extra_data` of a genesis block authorizing `signers`.
+pub fn genesis_extra_data(signers: &[Address]) -> Bytes {
+    let mut signers = signers.to_vec();
+    signers.sort();
+    let mut extra_data = vec![0; EXTRA_VANITY];
+    for signer in &signers {
+        extra_data.extend_from_slice(signer);
+    }
+    extra_data.extend_from_slice(&[0; EXTRA_SEAL]);
+    extra_data
+}
+
+/// The hash a signer signs: the sealed header with the signature stripped
+/// from `extra_data`.
+pub fn seal_hash(header: &Header) -> H256 {
+    let mut header = header.clone();
+    let len = header.extra_data.len().saturating_sub(EXTRA_SEAL);
+    header.extra_data.truncate(len);
+    header.hash()
+}
+
+pub fn recover_signer(header: &Header) -> Result<Address, BlockError> {
+    if header.extra_data.len() < EXTRA_VANITY + EXTRA_SEAL {
+        return Err(BlockError::MissingSignature);
+    }
+    let seal = &header.extra_data[header.extra_data.len() - EXTRA_SEAL..];
+    let signature = Signature {
+        r: H256::from(&seal[0..32]),
+        s: H256::from(&seal[32..64]),
+        v: seal[64],
+    };
+    let public = crypto::recover(&signature, &seal_hash(header)).map_err(BlockError::InvalidSealSignature)?;
+    Ok(crypto::public_to_address(&public))
+}
+
+fn extra_data_signers(extra_data: &[u8]) -> Result<Vec<Address>, BlockError> {
+    if extra_data.len() < EXTRA_VANITY {
+        return Err(BlockError::MissingVanity);
+    }
+    if extra_data.len() < EXTRA_VANITY + EXTRA_SEAL {
+        return Err(BlockError::MissingSignature);
+    }
+    let signers = &extra_data[EXTRA_VANITY..extra_data.len() - EXTRA_SEAL];
+    if !signers.len().is_multiple_of(20) {
+        return Err(BlockError::InvalidCheckpointSigners);
+    }
+    Ok(signers.chunks(20).map(Address::from).collect())
+}
+
+impl SealVerifier for Clique {
+    fn verify_seal(&self, header: &Header) -> Result<(), BlockError> {
+        let signers = extra_data_signers(&header.extra_data)?;
+        let checkpoint = self.is_checkpoint(header.number);
+        if !checkpoint && !signers.is_empty() {
+            return Err(BlockError::InvalidCheckpointSigners);
+        }
+        if header.nonce.len() != 8 {
+            return Err(BlockError::InvalidNonceLength {
+                expected: 8,
+                got: header.nonce.len(),
+            });
+        }
+        if header.nonce() != NONCE_AUTH && header.nonce() != NONCE_DROP {
+            return Err(BlockError::InvalidVote);
+        }
+        if checkpoint && (!header.coinbase.is_zero() || header.nonce() != NONCE_DROP) {
+            return Err(BlockError::CheckpointVote);
+        }
+        if !header.mix_digest.is_zero() {
+            return Err(BlockError::NonZeroMixDigest);
+        }
+        // there are no uncles without proof of work
+        if header.uncles_hash != KECCAK_EMPTY_LIST_RLP {
+            return Err(BlockError::InvalidUnclesHash {
+                expected: KECCAK_EMPTY_LIST_RLP,
+                got: header.uncles_hash,
+            });
+        }
+        Ok(())
+    }
+
+    /// Also records the signer snapshot of `header`, so headers have to be
+    /// verified in chain order.
+    fn verify_seal_family(&self, header: &Header, parent: &Header) -> Result<(), BlockError> {
+        let min = self.min_timestamp(parent)?;
+        if header.timestamp < min {
+            return Err(BlockError::TimestampTooEarly {
+                min,
+                got: header.timestamp,
+            });
+        }
+
+        let mut snapshot = self.snapshot(&parent.hash())?;
+        if self.is_checkpoint(header.number) {
+            let signers = extra_data_signers(&header.extra_data)?;
+            if !signers.iter().eq(snapshot.signers.iter()) {
+                return Err(BlockError::InvalidCheckpointSigners);
+            }
+        }
+
+        let signer = recover_signer(header)?;
+        let expected = U256::from(if snapshot.is_in_turn(header.number, &signer) {
+            DIFF_IN_TURN
+        } else {
+            DIFF_NO_TURN
+        });
+        snapshot.apply(header, signer, self.params.epoch)?;
+        if header.difficulty != expected {
+            return Err(BlockError::InvalidDifficulty {
+                expected,
+                got: header.difficulty,
+            });
+        }
+
+        self.insert_snapshot(header.hash(), snapshot);
+        Ok(())
+    }
+}
+
+impl Engine for Clique {
+    fn name(&self) -> &str {
+        "Clique"
+    }
+
+    fn prepare(&self, header: &mut Header, parent: &Header) -> Result<(), BlockError> {
+        let snapshot = self.snapshot(&parent.hash())?;
+        let checkpoint = self.is_checkpoint(header.number);
+
+        header.coinbase = Address::default();
+        let mut vote = NONCE_DROP;
+        if !checkpoint {
+            let proposals = self.proposals.read().expect("clique lock poisoned");
+            if let Some((candidate, authorize)) = proposals
+                .iter()
+                .find(|&(candidate, authorize)| snapshot.is_valid_vote(candidate, *authorize))
+            {
+                header.coinbase = *candidate;
+                vote = if *authorize { NONCE_AUTH } else { NONCE_DROP };
+            }
+        }
+        let mut nonce = vec![0; 8];
+        BigEndian::write_u64(&mut nonce, vote);
+        header.nonce = nonce;
+        header.mix_digest = H256::default();
+
+        let in_turn = self.signer
+            .read()
+            .expect("clique lock poisoned")
+            .as_ref()
+            .is_some_and(|&(address, _)| snapshot.is_in_turn(header.number, &address));
+        header.difficulty = U256::from(if in_turn { DIFF_IN_TURN } else { DIFF_NO_TURN });
+
+        header.extra_data.resize(EXTRA_VANITY, 0);
+        if checkpoint {
+            for signer in &snapshot.signers {
+                header.extra_data.extend_from_slice(signer);
+            }
+        }
+        header.extra_data.extend_from_slice(&[0; EXTRA_SEAL]);
+
+        header.timestamp = cmp::max(header.timestamp, self.min_timestamp(parent)?);
+        Ok(())
+    }
+
+    /// Signs with the local key, or returns `Seal::None` when there is none or
+    /// it may not sign this block.
+    fn seal(&self, header: &mut Header, parent: &Header) -> Seal {
+        let (address, secret) = match *self.signer.read().expect("clique lock poisoned") {
+            Some(signer) => signer,
+            None => return Seal::None,
+        };
+        // refuse to sign what our own verification would reject
+        let mut snapshot = match self.snapshot(&parent.hash()) {
+            Ok(snapshot) => snapshot,
+            Err(_) => return Seal::None,
+        };
+        if snapshot.apply(header, address, self.params.epoch).is_err() {
+            return Seal::None;
+        }
+        if header.extra_data.len() < EXTRA_VANITY + EXTRA_SEAL {
+            return Seal::None;
+        }
+
+        let signature = match crypto::sign(&secret, &seal_hash(header)) {
+            Ok(signature) => signature,
+            Err(_) => return Seal::None,
+        };
+        let offset = header.extra_data.len() - EXTRA_SEAL;
+        let seal = &mut header.extra_data[offset..];
+        seal[0..32].copy_from_slice(&signature.r);
+        seal[32..64].copy_from_slice(&signature.s);
+        seal[64] = signature.v;
+        Seal::Sealed
+    }
+
+    /// The generic extra data limit doesn't apply, `verify_seal` checks the
+    /// vanity, signer list and signature layout instead.
+    fn verify_header(
+        &self,
+        header: &Header,
+        parent: Option<&Header>,
+        params: &VerificationParams,
+    ) -> Result<(), BlockError> {
+        let params = VerificationParams {
+            maximum_extra_data_size: usize::MAX,
+            ..params.clone()
+        };
+        verification::verify_header(header, parent, &params, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+    use byteorder::{BigEndian, ByteOrder};
+    use ethereum_types::{Address, H256, U256};
+    use keccak_hash::KECCAK_EMPTY_LIST_RLP;
+    use crypto::{self, Secret};
+    use engines::{Engine, Seal};
+    use error::BlockError;
+    use header::Header;
+    use verification::{SealVerifier, VerificationParams};
+    use super::{genesis_extra_data, recover_signer, Clique, CliqueParams, Snapshot, NONCE_AUTH, NONCE_DROP};
+
+    fn secrets() -> Vec<Secret> {
+        vec![Secret::from(1), Secret::from(2), Secret::from(3)]
+    }
+
+    fn address(secret: &Secret) -> Address {
+        crypto::public_to_address(&crypto::secret_to_public(secret).unwrap())
+    }
+
+    fn genesis(signers: &[Address]) -> Header {
+        Header {
+            timestamp: 1,
+            gas_limit: U256::from(8_000_000),
+            extra_data: genesis_extra_data(signers),
+            nonce: vec![0; 8],
+            ..Header::default()
+        }
+    }
+
+    fn params() -> CliqueParams {
+        CliqueParams { period: 5, epoch: 100 }
+    }
+
+    fn build(engine: &Clique, parent: &Header, secret: &Secret) -> Result<Header, BlockError> {
+        engine.set_signer(*secret).unwrap();
+        let mut header = Header {
+            parent_hash: parent.hash(),
+            number: parent.number + 1,
+            gas_limit: parent.gas_limit,
+            ..Header::default()
+        };
+        engine.prepare(&mut header, parent)?;
+        assert_eq!(engine.seal(&mut header, parent), Seal::Sealed);
+        engine.verify_header(&header, Some(parent), &VerificationParams::default())?;
+        Ok(header)
+    }
+
+    /// Signer whose turn `number` is.
+    fn in_turn(signers: &[(Address, Secret)], number: u64) -> Secret {
+        signers[number as usize % signers.len()].1
+    }
+
+    fn sorted_signers(secrets: &[Secret]) -> Vec<(Address, Secret)> {
+        let mut signers: Vec<_> = secrets.iter().map(|s| (address(s), *s)).collect();
+        signers.sort();
+        signers
+    }
+
+    #[test]
+    fn test_in_turn_rotation() {
+        let signers = sorted_signers(&secrets());
+        let addresses: Vec<_> = signers.iter().map(|s| s.0).collect();
+        let genesis = genesis(&addresses);
+        let engine = Clique::new(params(), &genesis).unwrap();
+
+        let mut parent = genesis;
+        for _ in 0..6 {
+            let secret = in_turn(&signers, parent.number + 1);
+            let header = build(&engine, &parent, &secret).unwrap();
+            assert_eq!(header.difficulty, U256::from(2));
+            assert_eq!(header.timestamp, parent.timestamp + 5);
+            assert_eq!(recover_signer(&header).unwrap(), address(&secret));
+            parent = header;
+        }
+    }
+
+    #[test]
+    fn test_out_of_turn_and_recent_signers() {
+        let signers = sorted_signers(&secrets());
+        let addresses: Vec<_> = signers.iter().map(|s| s.0).collect();
+        let genesis = genesis(&addresses);
+        let engine = Clique::new(params(), &genesis).unwrap();
+
+        // block 1 is signer 1's turn, signer 2 seals it out of turn
+        let block1 = build(&engine, &genesis, &signers[2].1).unwrap();
+        assert_eq!(block1.difficulty, U256::one());
+
+        // with three signers the last two blocks' signers must wait
+        engine.set_signer(signers[2].1).unwrap();
+        let mut header = Header {
+            parent_hash: block1.hash(),
+            number: 2,
+            gas_limit: block1.gas_limit,
+            ..Header::default()
+        };
+        engine.prepare(&mut header, &block1).unwrap();
+        assert_eq!(engine.seal(&mut header, &block1), Seal::None);
+
+        let outsider = Secret::from(4);
+        engine.set_signer(outsider).unwrap();
+        assert_eq!(engine.seal(&mut header, &block1), Seal::None);
+
+        // a seal by a key outside the signer set is rejected on import
+        let mut forged = build(&engine, &genesis, &signers[0].1).unwrap();
+        let signature = crypto::sign(&outsider, &super::seal_hash(&forged)).unwrap();
+        let offset = forged.extra_data.len() - 65;
+        forged.extra_data[offset..offset + 32].copy_from_slice(&signature.r);
+        forged.extra_data[offset + 32..offset + 64].copy_from_slice(&signature.s);
+        forged.extra_data[offset + 64] = signature.v;
+        assert_eq!(
+            engine.verify_header(&forged, Some(&genesis), &VerificationParams::default()),
+            Err(BlockError::UnauthorizedSigner(address(&outsider)))
+        );
+    }
+
+    #[test]
+    fn test_vote_in_new_signer() {
+        let signers = sorted_signers(&secrets()[..2]);
+        let addresses: Vec<_> = signers.iter().map(|s| s.0).collect();
+        let genesis = genesis(&addresses);
+        let engine = Clique::new(params(), &genesis).unwrap();
+        let candidate = address(&secrets()[2]);
+        engine.propose(candidate, true);
+
+        // both signers have to vote before the candidate is in
+        let block1 = build(&engine, &genesis, &in_turn(&signers, 1)).unwrap();
+        assert_eq!(block1.coinbase, candidate);
+        assert_eq!(engine.signers(&block1.hash()).unwrap().len(), 2);
+        let block2 = build(&engine, &block1, &in_turn(&signers, 2)).unwrap();
+        let signers_after = engine.signers(&block2.hash()).unwrap();
+        assert!(signers_after.contains(&candidate));
+        assert_eq!(signers_after.len(), 3);
+
+        // the new signer can seal, and the passed vote isn't repeated
+        let block3 = build(&engine, &block2, &secrets()[2]).unwrap();
+        assert!(block3.coinbase.is_zero());
+    }
+
+    #[test]
+    fn test_tally_counts_valid_votes_in_one_direction() {
+        let (a, b, c, d, x) = (
+            Address::from(1),
+            Address::from(2),
+            Address::from(3),
+            Address::from(4),
+            Address::from(5),
+        );
+        let mut snapshot = Snapshot {
+            number: 0,
+            signers: vec![a, b, c, d].into_iter().collect::<BTreeSet<_>>(),
+            recents: BTreeMap::new(),
+            votes: vec![],
+        };
+        let vote = |number: u64, candidate: Address, authorize: bool| {
+            let mut nonce = vec![0; 8];
+            BigEndian::write_u64(&mut nonce, if authorize { NONCE_AUTH } else { NONCE_DROP });
+            Header {
+                number,
+                coinbase: candidate,
+                nonce,
+                ..Header::default()
+            }
+        };
+
+        snapshot.apply(&vote(1, x, true), a, 100).unwrap();
+        snapshot.apply(&vote(2, x, true), b, 100).unwrap();
+        snapshot.apply(&vote(3, d, false), c, 100).unwrap();
+        snapshot.apply(&vote(4, d, false), a, 100).unwrap();
+        snapshot.apply(&vote(5, d, false), b, 100).unwrap();
+        assert!(!snapshot.signers.contains(&d));
+
+        // dropping x is not a valid vote, it neither passes nor discards
+        // the two votes to authorize x
+        snapshot.apply(&vote(6, x, false), c, 100).unwrap();
+        assert!(!snapshot.signers.contains(&x));
+        assert_eq!(snapshot.votes.iter().filter(|v| v.candidate == x).count(), 2);
+
+        snapshot.apply(&vote(7, x, true), a, 100).unwrap();
+        assert!(snapshot.signers.contains(&x));
+        assert!(snapshot.votes.is_empty());
+    }
+
+    #[test]
+    fn test_snapshots_are_pruned_and_rebuilt() {
+        let signers = sorted_signers(&secrets());
+        let addresses: Vec<_> = signers.iter().map(|s| s.0).collect();
+        let genesis = genesis(&addresses);
+        let engine = Clique::new(params(), &genesis).unwrap();
+
+        let mut headers = vec![genesis.clone()];
+        for number in 1..140 {
+            let header = build(&engine, &headers[headers.len() - 1], &in_turn(&signers, number)).unwrap();
+            headers.push(header);
+        }
+        assert_eq!(engine.signers(&genesis.hash()), None);
+        assert_eq!(engine.snapshots.read().unwrap().len(), 128);
+
+        // a restarted node replays the headers since the last checkpoint
+        let restarted = Clique::new(params(), &genesis).unwrap();
+        let best = headers[139].clone();
+        let mut header = Header {
+            parent_hash: best.hash(),
+            number: 140,
+            gas_limit: best.gas_limit,
+            ..Header::default()
+        };
+        assert_eq!(
+            restarted.prepare(&mut header, &best),
+            Err(BlockError::UnknownParent(best.hash()))
+        );
+        assert_eq!(
+            restarted.import_headers(&headers[101..]),
+            Err(BlockError::UnknownParent(headers[101].hash()))
+        );
+        restarted.import_headers(&headers[100..]).unwrap();
+        assert_eq!(restarted.signers(&best.hash()), engine.signers(&best.hash()));
+        build(&restarted, &best, &in_turn(&signers, 140)).unwrap();
+    }
+
+    #[test]
+    fn test_reject_malformed_extra_data() {
+        let signers = sorted_signers(&secrets());
+        let addresses: Vec<_> = signers.iter().map(|s| s.0).collect();
+        let genesis = genesis(&addresses);
+        let engine = Clique::new(params(), &genesis).unwrap();
+
+        let mut header = build(&engine, &genesis, &in_turn(&signers, 1)).unwrap();
+        header.extra_data.truncate(40);
+        assert_eq!(
+            engine.verify_header(&header, Some(&genesis), &VerificationParams::default()),
+            Err(BlockError::MissingSignature)
+        );
+        header.extra_data = genesis.extra_data.clone();
+        assert_eq!(
+            engine.verify_header(&header, Some(&genesis), &VerificationParams::default()),
+            Err(BlockError::InvalidCheckpointSigners)
+        );
+    }
+
+    #[test]
+    fn test_reject_uncles() {
+        let signers = sorted_signers(&secrets());
+        let addresses: Vec<_> = signers.iter().map(|s| s.0).collect();
+        let genesis = genesis(&addresses);
+        let engine = Clique::new(params(), &genesis).unwrap();
+
+        let mut header = build(&engine, &genesis, &in_turn(&signers, 1)).unwrap();
+        assert_eq!(engine.verify_seal(&header), Ok(()));
+        header.uncles_hash = H256::from(1);
+        assert_eq!(
+            engine.verify_seal(&header),
+            Err(BlockError::InvalidUnclesHash {
+                expected: KECCAK_EMPTY_LIST_RLP,
+                got: H256::from(1),
+            })
+        );
+    }
+
+    #[test]
+    fn test_timestamp_overflow() {
+        let signers = sorted_signers(&secrets());
+        let addresses: Vec<_> = signers.iter().map(|s| s.0).collect();
+        let genesis = Header {
+            timestamp: u64::MAX - 1,
+            ..genesis(&addresses)
+        };
+        let engine = Clique::new(params(), &genesis).unwrap();
+        let mut header = Header {
+            parent_hash: genesis.hash(),
+            number: 1,
+            timestamp: u64::MAX,
+            ..Header::default()
+        };
+        assert_eq!(engine.prepare(&mut header, &genesis), Err(BlockError::TimestampOverflow));
+        assert_eq!(engine.verify_seal_family(&header, &genesis), Err(BlockError::TimestampOverflow));
+    }
+}
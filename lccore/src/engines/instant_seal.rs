@@ -0,0 +1,34 @@
+use ethereum_types::U256;
+use engines::{Engine, Seal};
+use error::BlockError;
+use header::Header;
+use verification::SealVerifier;
+
+/// Development engine that seals every block immediately with no proof of
+/// work. Each block is stamped one second after its parent, so a chain
+/// built from the same transactions is always identical.
+#[derive(Debug, Default)]
+pub struct InstantSeal;
+
+impl SealVerifier for InstantSeal {}
+
+impl Engine for InstantSeal {
+    fn name(&self) -> &str {
+        "InstantSeal"
+    }
+
+    fn prepare(&self, header: &mut Header, parent: &Header) -> Result<(), BlockError> {
+        header.timestamp = parent.timestamp.checked_add(1).ok_or(BlockError::TimestampOverflow)?;
+        header.difficulty = U256::one();
+        Ok(())
+    }
+
+    fn seal(&self, header: &mut Header, _parent: &Header) -> Seal {
+        header.set_seal(Default::default(), 0);
+        Seal::Sealed
+    }
+
+    fn seals_on_transaction(&self) -> bool {
+        true
+    }
+}
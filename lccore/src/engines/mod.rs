@@ -0,0 +1,47 @@
+//! Consensus engines: what a block's seal means, how to produce one and how
+//! to check it.
+
+mod clique;
+mod instant_seal;
+
+pub use self::clique::{genesis_extra_data, recover_signer, seal_hash, Clique, CliqueParams};
+pub use self::instant_seal::InstantSeal;
+
+use error::BlockError;
+use header::Header;
+use verification::{self, SealVerifier, VerificationParams};
+
+/// Outcome of an attempt to seal a prepared header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Seal {
+    /// The header now carries a valid seal.
+    Sealed,
+    /// This node can't seal the header, e.g. it is not an authorized signer.
+    None,
+}
+
+pub trait Engine: SealVerifier + Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Fills in the header fields the engine owns, such as difficulty,
+    /// before the header is sealed.
+    fn prepare(&self, header: &mut Header, parent: &Header) -> Result<(), BlockError>;
+
+    fn seal(&self, header: &mut Header, parent: &Header) -> Seal;
+
+    /// Whether a block should be sealed as soon as a transaction arrives,
+    /// rather than on the engine's own schedule.
+    fn seals_on_transaction(&self) -> bool {
+        false
+    }
+
+    /// Runs the whole verification pipeline with this engine's seal checks.
+    fn verify_header(
+        &self,
+        header: &Header,
+        parent: Option<&Header>,
+        params: &VerificationParams,
+    ) -> Result<(), BlockError> {
+        verification::verify_header(header, parent, params, self)
+    }
+}
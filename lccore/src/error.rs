@@ -0,0 +1,419 @@
+use std::error::Error as StdError;
+use std::{fmt, io};
+use ethereum_types::{Address, H256, U256};
+use crypto;
+use rlp::DecoderError;
+use trie::TrieError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionError {
+    InvalidSignature(crypto::Error),
+    InvalidChainId {
+        expected: Option<u64>,
+        got: Option<u64>,
+    },
+    /// Signature `s` is in the upper half of the curve order.
+    HighS,
+    /// Gas limit does not cover the intrinsic gas.
+    NotEnoughBaseGas {
+        required: U256,
+        got: U256,
+    },
+    TipAboveFeeCap {
+        max_priority_fee_per_gas: U256,
+        max_fee_per_gas: U256,
+    },
+    AlreadyImported,
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TransactionError::InvalidSignature(ref e) => write!(f, "invalid signature: {}", e),
+            TransactionError::InvalidChainId { expected, got } => write!(
+                f,
+                "invalid chain id, expected {:?}, got {:?}",
+                expected, got
+            ),
+            TransactionError::HighS => write!(f, "signature s is in the upper half of the curve order"),
+            TransactionError::NotEnoughBaseGas { required, got } => write!(
+                f,
+                "not enough base gas, required {}, got {}",
+                required, got
+            ),
+            TransactionError::TipAboveFeeCap {
+                max_priority_fee_per_gas,
+                max_fee_per_gas,
+            } => write!(
+                f,
+                "max priority fee per gas {} above max fee per gas {}",
+                max_priority_fee_per_gas, max_fee_per_gas
+            ),
+            TransactionError::AlreadyImported => write!(f, "transaction already imported"),
+        }
+    }
+}
+
+impl StdError for TransactionError {}
+
+impl From<crypto::Error> for TransactionError {
+    fn from(e: crypto::Error) -> Self {
+        TransactionError::InvalidSignature(e)
+    }
+}
+
+/// A header failed one of the verification rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockError {
+    ExtraDataTooLong {
+        max: usize,
+        got: usize,
+    },
+    TooMuchGasUsed {
+        gas_limit: U256,
+        gas_used: U256,
+    },
+    ZeroTimestamp,
+    InvalidNumber {
+        expected: u64,
+        got: u64,
+    },
+    /// The parent is at `u64::MAX`, a child's number doesn't fit.
+    NumberOverflow,
+    /// Timestamp is not strictly after the parent's.
+    InvalidTimestamp {
+        parent: u64,
+        got: u64,
+    },
+    /// Gas limit moved further from the parent's than the bound divisor allows,
+    /// or fell below the minimum. `min` and `max` are inclusive.
+    InvalidGasLimit {
+        min: U256,
+        max: U256,
+        got: U256,
+    },
+    InvalidParentHash {
+        expected: H256,
+        got: H256,
+    },
+    InvalidDifficulty {
+        expected: U256,
+        got: U256,
+    },
+    InvalidNonceLength {
+        expected: usize,
+        got: usize,
+    },
+    /// The seal's final hash is above the difficulty boundary.
+    InvalidProofOfWork {
+        boundary: H256,
+        got: H256,
+    },
+    MismatchedMixDigest {
+        expected: H256,
+        got: H256,
+    },
+    /// Authority engines: the block came sooner than the block period allows.
+    TimestampTooEarly {
+        min: u64,
+        got: u64,
+    },
+    /// The parent's timestamp leaves no room for a child's.
+    TimestampOverflow,
+    /// Clique extra data lacks the 32 byte vanity prefix.
+    MissingVanity,
+    /// Clique extra data lacks the 65 byte signer signature.
+    MissingSignature,
+    /// Signer list on a non-checkpoint block, or one not matching the
+    /// current signers on a checkpoint.
+    InvalidCheckpointSigners,
+    /// Vote nonce is neither authorize nor drop.
+    InvalidVote,
+    /// Checkpoint blocks can't carry a vote.
+    CheckpointVote,
+    NonZeroMixDigest,
+    InvalidSealSignature(crypto::Error),
+    UnauthorizedSigner(Address),
+    /// The signer sealed one of the most recent blocks.
+    RecentlySigned(Address),
+    /// No signer snapshot for the parent, headers must be verified in order.
+    UnknownParent(H256),
+    TooManyUncles {
+        max: usize,
+        got: usize,
+    },
+    InvalidUnclesHash {
+        expected: H256,
+        got: H256,
+    },
+    DuplicateUncle(H256),
+    /// The uncle is the block itself or one of its ancestors.
+    UncleIsAncestor(H256),
+    /// The uncle was already included by an ancestor.
+    UncleAlreadyIncluded(H256),
+    /// Uncle number not within the allowed depth below the block, bounds
+    /// are inclusive.
+    UncleOutOfBounds {
+        min: u64,
+        max: u64,
+        got: u64,
+    },
+    /// The uncle's parent is not a recent ancestor of the block.
+    UncleParentNotInChain(H256),
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BlockError::ExtraDataTooLong { max, got } => write!(
+                f,
+                "extra data too long, max {} bytes, got {}",
+                max, got
+            ),
+            BlockError::TooMuchGasUsed { gas_limit, gas_used } => write!(
+                f,
+                "gas used {} above gas limit {}",
+                gas_used, gas_limit
+            ),
+            BlockError::ZeroTimestamp => write!(f, "timestamp is zero"),
+            BlockError::InvalidNumber { expected, got } => write!(
+                f,
+                "invalid block number, expected {}, got {}",
+                expected, got
+            ),
+            BlockError::NumberOverflow => write!(f, "block number overflows u64"),
+            BlockError::InvalidTimestamp { parent, got } => write!(
+                f,
+                "timestamp {} not after parent timestamp {}",
+                got, parent
+            ),
+            BlockError::InvalidGasLimit { min, max, got } => write!(
+                f,
+                "gas limit {} out of bounds [{}, {}]",
+                got, min, max
+            ),
+            BlockError::InvalidParentHash { expected, got } => write!(
+                f,
+                "invalid parent hash, expected {:?}, got {:?}",
+                expected, got
+            ),
+            BlockError::InvalidDifficulty { expected, got } => write!(
+                f,
+                "invalid difficulty, expected {}, got {}",
+                expected, got
+            ),
+            BlockError::InvalidNonceLength { expected, got } => write!(
+                f,
+                "invalid nonce length, expected {} bytes, got {}",
+                expected, got
+            ),
+            BlockError::InvalidProofOfWork { boundary, got } => write!(
+                f,
+                "proof of work {:?} above boundary {:?}",
+                got, boundary
+            ),
+            BlockError::MismatchedMixDigest { expected, got } => write!(
+                f,
+                "mismatched mix digest, expected {:?}, got {:?}",
+                expected, got
+            ),
+            BlockError::TimestampTooEarly { min, got } => write!(
+                f,
+                "timestamp {} before the earliest allowed {}",
+                got, min
+            ),
+            BlockError::TimestampOverflow => write!(f, "timestamp overflows u64"),
+            BlockError::MissingVanity => write!(f, "extra data missing vanity"),
+            BlockError::MissingSignature => write!(f, "extra data missing signer signature"),
+            BlockError::InvalidCheckpointSigners => write!(f, "invalid signer list in extra data"),
+            BlockError::InvalidVote => write!(f, "nonce is not a valid vote"),
+            BlockError::CheckpointVote => write!(f, "vote on a checkpoint block"),
+            BlockError::NonZeroMixDigest => write!(f, "mix digest is not zero"),
+            BlockError::InvalidSealSignature(ref e) => write!(f, "invalid seal signature: {}", e),
+            BlockError::UnauthorizedSigner(ref signer) => write!(f, "unauthorized signer {:?}", signer),
+            BlockError::RecentlySigned(ref signer) => write!(f, "{:?} signed too recently", signer),
+            BlockError::UnknownParent(ref hash) => write!(f, "unknown parent {:?}", hash),
+            BlockError::TooManyUncles { max, got } => write!(f, "too many uncles, max {}, got {}", max, got),
+            BlockError::InvalidUnclesHash { expected, got } => write!(
+                f,
+                "invalid uncles hash, expected {:?}, got {:?}",
+                expected, got
+            ),
+            BlockError::DuplicateUncle(ref hash) => write!(f, "duplicate uncle {:?}", hash),
+            BlockError::UncleIsAncestor(ref hash) => write!(f, "uncle {:?} is an ancestor", hash),
+            BlockError::UncleAlreadyIncluded(ref hash) => write!(f, "uncle {:?} already included", hash),
+            BlockError::UncleOutOfBounds { min, max, got } => write!(
+                f,
+                "uncle number {} out of bounds [{}, {}]",
+                got, min, max
+            ),
+            BlockError::UncleParentNotInChain(ref hash) => {
+                write!(f, "uncle parent {:?} is not a recent ancestor", hash)
+            }
+        }
+    }
+}
+
+impl StdError for BlockError {}
+
+/// A submitted transaction was rejected, or accepted but sealing it failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProducerError {
+    Transaction(TransactionError),
+    Block(BlockError),
+}
+
+impl fmt::Display for ProducerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProducerError::Transaction(ref e) => write!(f, "transaction rejected: {}", e),
+            ProducerError::Block(ref e) => write!(f, "sealing failed: {}", e),
+        }
+    }
+}
+
+impl StdError for ProducerError {}
+
+impl From<TransactionError> for ProducerError {
+    fn from(e: TransactionError) -> Self {
+        ProducerError::Transaction(e)
+    }
+}
+
+impl From<BlockError> for ProducerError {
+    fn from(e: BlockError) -> Self {
+        ProducerError::Block(e)
+    }
+}
+
+/// A block list that doesn't decode.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BlockDecodeError {
+    /// The list is not `[header, transactions, uncles]`, such as the two
+    /// item layout older linkchain versions wrote.
+    MismatchedLayout { items: usize },
+    Rlp(DecoderError),
+}
+
+impl fmt::Display for BlockDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BlockDecodeError::MismatchedLayout { items } => write!(
+                f,
+                "block is not encoded as [header, transactions, uncles], got {} items",
+                items
+            ),
+            BlockDecodeError::Rlp(ref e) => write!(f, "invalid block rlp: {}", e),
+        }
+    }
+}
+
+impl StdError for BlockDecodeError {}
+
+impl From<DecoderError> for BlockDecodeError {
+    fn from(e: DecoderError) -> Self {
+        BlockDecodeError::Rlp(e)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StateError {
+    Trie(TrieError),
+    InsufficientBalance {
+        required: U256,
+        got: U256,
+    },
+    /// Adding `amount` would take the balance past `U256::max_value()`.
+    BalanceOverflow {
+        balance: U256,
+        amount: U256,
+    },
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::Trie(ref e) => write!(f, "state trie: {}", e),
+            StateError::InsufficientBalance { required, got } => write!(
+                f,
+                "insufficient balance, required {}, got {}",
+                required, got
+            ),
+            StateError::BalanceOverflow { balance, amount } => write!(
+                f,
+                "adding {} to balance {} overflows",
+                amount, balance
+            ),
+        }
+    }
+}
+
+impl StdError for StateError {}
+
+impl From<TrieError> for StateError {
+    fn from(e: TrieError) -> Self {
+        StateError::Trie(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum ChainError {
+    Io(io::Error),
+    /// Stored chain data that doesn't decode.
+    Corrupt(DecoderError),
+    UnknownParent(H256),
+    AlreadyInChain(H256),
+    /// A block whose number isn't one more than its parent's.
+    InvalidNumber {
+        parent: u64,
+        got: u64,
+    },
+    /// A stored header without its body.
+    MissingBody(H256),
+    /// The block's difficulty takes its chain's total past `U256::max_value()`.
+    TotalDifficultyOverflow(H256),
+    /// The database holds a chain with another genesis block.
+    GenesisMismatch {
+        expected: H256,
+        got: H256,
+    },
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChainError::Io(ref e) => write!(f, "chain database: {}", e),
+            ChainError::Corrupt(ref e) => write!(f, "corrupt chain data: {}", e),
+            ChainError::UnknownParent(ref hash) => write!(f, "unknown parent {:?}", hash),
+            ChainError::AlreadyInChain(ref hash) => write!(f, "block {:?} already in chain", hash),
+            ChainError::InvalidNumber { parent, got } => write!(
+                f,
+                "block number {} doesn't follow parent number {}",
+                got, parent
+            ),
+            ChainError::MissingBody(ref hash) => write!(f, "missing body of block {:?}", hash),
+            ChainError::TotalDifficultyOverflow(ref hash) => {
+                write!(f, "total difficulty overflows at block {:?}", hash)
+            }
+            ChainError::GenesisMismatch { ref expected, ref got } => write!(
+                f,
+                "genesis mismatch, expected {:?}, got {:?}",
+                expected, got
+            ),
+        }
+    }
+}
+
+impl StdError for ChainError {}
+
+impl From<io::Error> for ChainError {
+    fn from(e: io::Error) -> Self {
+        ChainError::Io(e)
+    }
+}
+
+impl From<DecoderError> for ChainError {
+    fn from(e: DecoderError) -> Self {
+        ChainError::Corrupt(e)
+    }
+}
@@ -1,12 +1,40 @@
 use ethereum_types::{Address, Bloom, H256, U256};
 use bytes::Bytes;
 use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
-use keccak_hash::{keccak, KECCAK_NULL_RLP};
+use keccak_hash::{keccak, KECCAK_EMPTY_LIST_RLP, KECCAK_NULL_RLP};
 use byteorder::{BigEndian, ByteOrder};
 
+const BARE_ITEM_COUNT: usize = 13;
+const SEAL_ITEM_COUNT: usize = 2;
+
+/// Fields added by London (EIP-1559) and the forks built on it. Each fork
+/// only adds its fields on top of the previous one's, so a header can't
+/// hold a later field without the earlier ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LondonFields {
+    pub base_fee_per_gas: U256,
+    pub shanghai: Option<ShanghaiFields>,
+}
+
+/// EIP-4895, present from Shanghai on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShanghaiFields {
+    pub withdrawals_root: H256,
+    pub cancun: Option<CancunFields>,
+}
+
+/// EIP-4844 and EIP-4788, present from Cancun on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CancunFields {
+    pub blob_gas_used: U256,
+    pub excess_blob_gas: U256,
+    pub parent_beacon_block_root: H256,
+}
+
 #[derive(Debug, Clone, Eq)]
 pub struct Header {
     pub parent_hash: H256,
+    pub uncles_hash: H256,
     pub coinbase: Address,
     pub state_root: H256,
     pub receipts_root: H256,
@@ -20,18 +48,21 @@ pub struct Header {
     pub extra_data: Bytes,
     pub mix_digest: H256,
     pub nonce: Bytes,
+    /// Trailing fork fields, present from London on.
+    pub london: Option<LondonFields>,
 }
 
 impl PartialEq for Header {
     fn eq(&self, c: &Header) -> bool {
-        self.parent_hash == c.parent_hash && self.coinbase == c.coinbase
+        self.parent_hash == c.parent_hash && self.uncles_hash == c.uncles_hash
+            && self.coinbase == c.coinbase
             && self.state_root == c.state_root && self.receipts_root == c.receipts_root
             && self.transactions_root == c.transactions_root
             && self.log_bloom == c.log_bloom && self.difficulty == c.difficulty
             && self.number == c.number && self.gas_used == c.gas_used
             && self.gas_limit == c.gas_limit && self.timestamp == c.timestamp
             && self.extra_data == c.extra_data && self.mix_digest == c.mix_digest
-            && self.nonce == c.nonce
+            && self.nonce == c.nonce && self.london == c.london
     }
 }
 
@@ -39,6 +70,7 @@ impl Default for Header {
     fn default() -> Self {
         Header {
             parent_hash: H256::default(),
+            uncles_hash: KECCAK_EMPTY_LIST_RLP,
             coinbase: Address::default(),
             state_root: KECCAK_NULL_RLP,
             receipts_root: KECCAK_NULL_RLP,
@@ -52,6 +84,7 @@ impl Default for Header {
             extra_data: vec![],
             mix_digest: H256::default(),
             nonce: vec![],
+            london: None,
         }
     }
 }
@@ -64,6 +97,9 @@ impl Header {
     pub fn parent_hash(&self) -> &H256 {
         &self.parent_hash
     }
+    pub fn uncles_hash(&self) -> &H256 {
+        &self.uncles_hash
+    }
     pub fn timestamp(&self) -> u64 {
         self.timestamp
     }
@@ -98,12 +134,63 @@ impl Header {
         &self.difficulty
     }
     pub fn nonce(&self) -> u64 {
-        BigEndian::read_u64(&self.nonce.as_slice()[0..8])
+        match self.nonce.len() {
+            0 => 0,
+            n if n < 8 => BigEndian::read_uint(&self.nonce, n),
+            _ => BigEndian::read_u64(&self.nonce[0..8]),
+        }
+    }
+
+    /// Sets the proof of work seal, `nonce` is stored as 8 big-endian bytes.
+    pub fn set_seal(&mut self, mix_digest: H256, nonce: u64) {
+        let mut bytes = vec![0; 8];
+        BigEndian::write_u64(&mut bytes, nonce);
+        self.mix_digest = mix_digest;
+        self.nonce = bytes;
+    }
+
+    /// Hash of the full header, the block identity.
+    pub fn hash(&self) -> H256 {
+        self.rlp_keccak(true)
+    }
+
+    /// Hash of the header without its seal fields, the value a seal commits to.
+    pub fn bare_hash(&self) -> H256 {
+        self.rlp_keccak(false)
+    }
+
+    pub fn base_fee_per_gas(&self) -> Option<&U256> {
+        self.london.as_ref().map(|london| &london.base_fee_per_gas)
+    }
+
+    fn shanghai(&self) -> Option<&ShanghaiFields> {
+        self.london.as_ref().and_then(|london| london.shanghai.as_ref())
+    }
+
+    fn cancun(&self) -> Option<&CancunFields> {
+        self.shanghai().and_then(|shanghai| shanghai.cancun.as_ref())
+    }
+
+    /// Number of trailing fork fields that get encoded.
+    fn trailing_item_count(&self) -> usize {
+        if self.cancun().is_some() {
+            5
+        } else if self.shanghai().is_some() {
+            2
+        } else if self.london.is_some() {
+            1
+        } else {
+            0
+        }
     }
 
     pub fn stream_rlp(&self, s: &mut RlpStream, with_seal: bool) {
-        s.begin_list(13 + if with_seal { 2 } else { 0 });
+        let trailing = self.trailing_item_count();
+        s.begin_list(
+            BARE_ITEM_COUNT + if with_seal { SEAL_ITEM_COUNT } else { 0 } + trailing,
+        );
         s.append(&self.parent_hash);
+        s.append(&self.uncles_hash);
         s.append(&self.coinbase);
         s.append(&self.state_root);
         s.append(&self.transactions_root);
@@ -117,7 +204,18 @@ impl Header {
         s.append(&self.extra_data);
         if with_seal {
             s.append(&self.mix_digest);
-            s.append(&&self.nonce[0..8]);
+            s.append(&self.nonce);
+        }
+        if let Some(ref london) = self.london {
+            s.append(&london.base_fee_per_gas);
+        }
+        if let Some(shanghai) = self.shanghai() {
+            s.append(&shanghai.withdrawals_root);
+        }
+        if let Some(cancun) = self.cancun() {
+            s.append(&cancun.blob_gas_used);
+            s.append(&cancun.excess_blob_gas);
+            s.append(&cancun.parent_beacon_block_root);
         }
     }
 
@@ -130,31 +228,75 @@ impl Header {
     pub fn rlp_keccak(&self, with_seal: bool) -> H256 {
         keccak(self.rlp(with_seal))
     }
-}
 
-impl Decodable for Header {
-    fn decode(r: &UntrustedRlp) -> Result<Self, DecoderError> {
+    /// Decodes a header written by `stream_rlp(s, with_seal)`, including
+    /// any trailing fork fields. A bare header decodes with an empty seal.
+    pub fn decode_rlp(r: &UntrustedRlp, with_seal: bool) -> Result<Self, DecoderError> {
+        let fixed_count = BARE_ITEM_COUNT + if with_seal { SEAL_ITEM_COUNT } else { 0 };
         let item_count = r.item_count()?;
-        if r.item_count()? != 14 {
-            Err(DecoderError::RlpIncorrectListLen)
+        // London adds one field, Shanghai one more and Cancun three
+        match item_count.checked_sub(fixed_count) {
+            Some(0) | Some(1) | Some(2) | Some(5) => {}
+            _ => return Err(DecoderError::RlpIncorrectListLen),
+        }
+
+        let mut header = Header {
+            parent_hash: r.val_at(0)?,
+            uncles_hash: r.val_at(1)?,
+            coinbase: r.val_at(2)?,
+            state_root: r.val_at(3)?,
+            transactions_root: r.val_at(4)?,
+            receipts_root: r.val_at(5)?,
+            log_bloom: r.val_at(6)?,
+            difficulty: r.val_at(7)?,
+            number: r.val_at(8)?,
+            gas_limit: r.val_at(9)?,
+            gas_used: r.val_at(10)?,
+            timestamp: r.val_at::<U256>(11)?.as_u64(),
+            extra_data: r.val_at(12)?,
+            mix_digest: H256::default(),
+            nonce: vec![],
+            london: None,
+        };
+        if with_seal {
+            header.mix_digest = r.val_at(13)?;
+            header.nonce = r.val_at(14)?;
+        }
+        let cancun = if item_count > fixed_count + 2 {
+            Some(CancunFields {
+                blob_gas_used: r.val_at(fixed_count + 2)?,
+                excess_blob_gas: r.val_at(fixed_count + 3)?,
+                parent_beacon_block_root: r.val_at(fixed_count + 4)?,
+            })
         } else {
-            Ok(Header {
-                parent_hash: r.val_at(0)?,
-                coinbase: r.val_at(1)?,
-                state_root: r.val_at(2)?,
-                transactions_root: r.val_at(3)?,
-                receipts_root: r.val_at(4)?,
-                log_bloom: r.val_at(5)?,
-                difficulty: r.val_at(6)?,
-                number: r.val_at(7)?,
-                gas_limit: r.val_at(8)?,
-                gas_used: r.val_at(9)?,
-                timestamp: r.val_at::<U256>(10)?.as_u64(),
-                extra_data: r.val_at(11)?,
-                mix_digest: r.val_at(12)?,
-                nonce: r.val_at(13)?,
+            None
+        };
+        let shanghai = if item_count > fixed_count + 1 {
+            Some(ShanghaiFields {
+                withdrawals_root: r.val_at(fixed_count + 1)?,
+                cancun,
             })
+        } else {
+            None
+        };
+        if item_count > fixed_count {
+            header.london = Some(LondonFields {
+                base_fee_per_gas: r.val_at(fixed_count)?,
+                shanghai,
+            });
         }
+        Ok(header)
+    }
+}
+
+impl Decodable for Header {
+    /// Accepts both the sealed form and the bare form written by
+    /// `stream_rlp(s, false)`, a bare header decodes with an empty seal.
+    /// A bare header with trailing fork fields can't be told apart from a
+    /// sealed one by its length alone, use `Header::decode_rlp` for those.
+    fn decode(r: &UntrustedRlp) -> Result<Self, DecoderError> {
+        let with_seal = r.item_count()? != BARE_ITEM_COUNT;
+        Header::decode_rlp(r, with_seal)
     }
 }
 
@@ -168,14 +310,20 @@ impl Encodable for Header {
 mod tests {
     use rustc_hex::FromHex;
     use rlp;
-    use super::Header;
+    use super::{CancunFields, Header, LondonFields, ShanghaiFields};
     use ethereum_types::{Address, Bloom, H256, U256};
     use byteorder::{BigEndian, ByteOrder};
     use bytes::Bytes;
+    use keccak_hash::{keccak, KECCAK_EMPTY_LIST_RLP};
+    use rlp::{DecoderError, RlpStream, UntrustedRlp};
+
+    fn frontier_header_rlp() -> Vec<u8> {
+        "f901f9a083cafc574e1f51ba9dc0568fc617a08ea2429fb384059c972f13b19fa1c8dd55a01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347948888f1f195afa192cfee860698584c030f4c9db1a0ef1552a40b7165c3cd773806b9e0c165b75356e0314bf0706f279c729f51e017a05fe50b260da6308036625b850b5d6ced6d0a9f814c0688bc91ffb7b7a3a54b67a0bc37d79753ad738a6dac4921e57392f145d8887476de3f783dfa7edae9283e52b90100000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000008302000001832fefd8825208845506eb0780a0bd4472abb6659ebe3ee06ee4d7b72a00a9f4d001caca51342001075469aff49888a13a5a8c8f2bb1c4".from_hex().unwrap()
+    }
 
     #[test]
     fn test_header_decode() {
-        let header_rlp = "f901d8a083cafc574e1f51ba9dc0568fc617a08ea2429fb384059c972f13b19fa1c8dd55948888f1f195afa192cfee860698584c030f4c9db1a0ef1552a40b7165c3cd773806b9e0c165b75356e0314bf0706f279c729f51e017a05fe50b260da6308036625b850b5d6ced6d0a9f814c0688bc91ffb7b7a3a54b67a0bc37d79753ad738a6dac4921e57392f145d8887476de3f783dfa7edae9283e52b90100000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000008302000001832fefd8825208845506eb0780a0bd4472abb6659ebe3ee06ee4d7b72a00a9f4d001caca51342001075469aff49888a13a5a8c8f2bb1c4".from_hex().unwrap();
+        let header_rlp = frontier_header_rlp();
         let expected_header: Header = rlp::decode(&header_rlp);
         let mut nonce: Bytes = vec![0; 8];
         BigEndian::write_u64(&mut nonce, 0xa13a5a8c8f2bb1c4);
@@ -186,6 +334,7 @@ mod tests {
                     .unwrap()
                     .as_slice(),
             ),
+            uncles_hash: KECCAK_EMPTY_LIST_RLP,
             coinbase: Address::from(
                 "8888f1f195afa192cfee860698584c030f4c9db1"
                     .from_hex()
@@ -223,8 +372,126 @@ mod tests {
                     .unwrap()
                     .as_slice(),
             ),
-            nonce: nonce,
+            nonce,
+            london: None,
         };
         assert_eq!(header, expected_header);
     }
+
+    #[test]
+    fn test_header_encode_round_trip() {
+        let header_rlp = frontier_header_rlp();
+        let header: Header = rlp::decode(&header_rlp);
+        assert_eq!(header.rlp(true), header_rlp);
+        assert_eq!(rlp::decode::<Header>(&rlp::encode(&header)), header);
+    }
+
+    #[test]
+    fn test_header_hash_and_bare_round_trip() {
+        let header_rlp = frontier_header_rlp();
+        let header: Header = rlp::decode(&header_rlp);
+        assert_eq!(header.rlp(true), header_rlp);
+        assert_eq!(header.hash(), keccak(&header_rlp));
+        assert_eq!(header.nonce(), 0xa13a5a8c8f2bb1c4);
+
+        let bare_rlp = header.rlp(false);
+        assert_eq!(header.bare_hash(), keccak(&bare_rlp));
+        assert!(header.bare_hash() != header.hash());
+
+        let bare: Header = rlp::decode(&bare_rlp);
+        assert_eq!(bare.rlp(false), bare_rlp);
+        assert_eq!(bare.bare_hash(), header.bare_hash());
+        assert_eq!(bare.mix_digest, H256::default());
+        assert_eq!(bare.nonce(), 0);
+    }
+
+    #[test]
+    fn test_fork_fields_round_trip() {
+        let mut header = Header {
+            number: 19_426_587,
+            gas_limit: U256::from(30_000_000),
+            nonce: vec![0; 8],
+            ..Header::default()
+        };
+        let mut encodings = vec![header.rlp(true)];
+
+        header.london = Some(LondonFields {
+            base_fee_per_gas: U256::from(7),
+            shanghai: None,
+        });
+        encodings.push(header.rlp(true));
+        header.london.as_mut().unwrap().shanghai = Some(ShanghaiFields {
+            withdrawals_root: H256::from(1),
+            cancun: None,
+        });
+        encodings.push(header.rlp(true));
+        header.london.as_mut().unwrap().shanghai.as_mut().unwrap().cancun = Some(CancunFields {
+            blob_gas_used: U256::from(0x20000),
+            excess_blob_gas: U256::zero(),
+            parent_beacon_block_root: H256::from(2),
+        });
+        encodings.push(header.rlp(true));
+
+        for (encoded, &trailing) in encodings.iter().zip(&[0, 1, 2, 5]) {
+            assert_eq!(rlp::UntrustedRlp::new(encoded).item_count().unwrap(), 15 + trailing);
+            let decoded: Header = rlp::decode(encoded);
+            assert_eq!(&decoded.rlp(true), encoded);
+        }
+        let decoded: Header = rlp::decode(&encodings[3]);
+        assert_eq!(decoded, header);
+
+        // the bare form needs the seal flag to be told apart
+        for encoded in &encodings {
+            let sealed: Header = rlp::decode(encoded);
+            let bare_rlp = sealed.rlp(false);
+            let decoded = Header::decode_rlp(&UntrustedRlp::new(&bare_rlp), false).unwrap();
+            assert_eq!(decoded.rlp(false), bare_rlp);
+            assert_eq!(decoded.bare_hash(), sealed.bare_hash());
+        }
+        assert_eq!(
+            Header::decode_rlp(&UntrustedRlp::new(&encodings[3]), false),
+            Err(DecoderError::RlpIncorrectListLen)
+        );
+    }
+
+    #[test]
+    fn test_partial_cancun_fields_are_rejected() {
+        let header_rlp = frontier_header_rlp();
+        let header = UntrustedRlp::new(&header_rlp);
+        // Shanghai plus only part of Cancun's three fields
+        for trailing in 3..5 {
+            let mut s = RlpStream::new_list(15 + trailing);
+            for item in header.iter() {
+                s.append_raw(item.as_raw(), 1);
+            }
+            for _ in 0..trailing {
+                s.append(&U256::from(7));
+            }
+            assert_eq!(
+                UntrustedRlp::new(&s.out()).as_val::<Header>(),
+                Err(DecoderError::RlpIncorrectListLen)
+            );
+        }
+    }
+
+    #[test]
+    fn test_london_header_hash() {
+        // go-ethereum's EIP-1559 block encoding vector
+        let mut header: Header = rlp::decode(&frontier_header_rlp());
+        header.london = Some(LondonFields {
+            base_fee_per_gas: U256::from(1_000_000_000),
+            shanghai: None,
+        });
+        let encoded = header.rlp(true);
+        assert_eq!(UntrustedRlp::new(&encoded).item_count().unwrap(), 16);
+        assert_eq!(
+            header.hash(),
+            "c7252048cd273fe0dac09650027d07f0e3da4ee0675ebbb26627cea92729c372".into()
+        );
+        assert_eq!(rlp::decode::<Header>(&encoded), header);
+
+        let bare = Header::decode_rlp(&UntrustedRlp::new(&header.rlp(false)), false).unwrap();
+        assert_eq!(bare.base_fee_per_gas(), header.base_fee_per_gas());
+        assert_eq!(bare.bare_hash(), header.bare_hash());
+    }
 }
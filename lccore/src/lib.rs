@@ -1,12 +1,74 @@
-#[macro_use]
+//! Core chain types for linkchain: block headers, transactions and blocks
+//! together with their RLP encoding.
+
+#[cfg(test)]
 extern crate rustc_hex;
 
 extern crate byteorder;
 extern crate bytes;
+extern crate ethash;
 extern crate ethereum_types;
 extern crate keccak_hash;
+extern crate kvdb;
+#[macro_use]
+extern crate lazy_static;
 extern crate rlp;
+extern crate secp256k1;
+extern crate trie;
+
+pub mod account;
+pub mod blockchain;
+pub mod contract_address;
+pub mod crypto;
+pub mod difficulty;
+pub mod engines;
+pub mod error;
+pub mod header;
+pub mod miner;
+pub mod pool;
+pub mod pow;
+pub mod producer;
+pub mod receipt;
+pub mod schedule;
+pub mod state;
+pub mod transaction;
+pub mod typed_transaction;
+pub mod verification;
+pub mod block;
+
+pub use account::Account;
+pub use block::Block;
+pub use blockchain::{BlockChain, ImportRoute, TreeRoute};
+pub use contract_address::{contract_address, create2_address};
+pub use difficulty::DifficultyParams;
+pub use engines::{Clique, CliqueParams, Engine, InstantSeal, Seal};
+pub use error::{BlockDecodeError, BlockError, ChainError, ProducerError, StateError, TransactionError};
+pub use header::{CancunFields, Header, LondonFields, ShanghaiFields};
+pub use miner::{Miner, MinerOptions, PowMode};
+pub use pool::TransactionPool;
+pub use pow::Ethash;
+pub use producer::BlockProducer;
+pub use receipt::{LogEntry, Receipt};
+pub use schedule::Schedule;
+pub use state::State;
+pub use transaction::{Recipient, SignedTransaction, Transaction, UnverifiedTransaction};
+pub use typed_transaction::{AccessList, AccessListItem, AccessListTransaction, DynamicFeeTransaction,
+                            TypedTransaction};
 
-mod header;
-mod transaction;
-mod block;
+/// Commonly used lccore types, to be glob imported by downstream crates.
+pub mod prelude {
+    pub use account::Account;
+    pub use block::Block;
+    pub use blockchain::BlockChain;
+    pub use bytes::Bytes;
+    pub use ethereum_types::{Address, Bloom, H256, U256};
+    pub use header::Header;
+    pub use miner::{Miner, MinerOptions, PowMode};
+    pub use pool::TransactionPool;
+    pub use pow::Ethash;
+    pub use producer::BlockProducer;
+    pub use receipt::{LogEntry, Receipt};
+    pub use state::State;
+    pub use transaction::{Recipient, SignedTransaction, Transaction, UnverifiedTransaction};
+    pub use typed_transaction::TypedTransaction;
+}
@@ -0,0 +1,163 @@
+//! Multi-threaded CPU miner for ethash seals.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use ethash::{self, EthashManager, Full, ProofOfWork};
+use ethereum_types::H256;
+use header::Header;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowMode {
+    /// Hash against the light cache, slow per nonce but needs no dataset.
+    Light,
+    /// Build the full dataset of each epoch before mining it.
+    Full,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinerOptions {
+    pub threads: usize,
+    pub pow_mode: PowMode,
+}
+
+impl Default for MinerOptions {
+    fn default() -> Self {
+        MinerOptions {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            pow_mode: PowMode::Light,
+        }
+    }
+}
+
+/// Searches nonces until the header's final hash meets its difficulty
+/// boundary. Seals verify with `pow::Ethash` built on the same manager.
+pub struct Miner {
+    manager: Arc<EthashManager>,
+    options: MinerOptions,
+    full: Mutex<Option<Arc<Full>>>,
+}
+
+impl Miner {
+    pub fn new(manager: Arc<EthashManager>, options: MinerOptions) -> Self {
+        Miner {
+            manager,
+            options,
+            full: Mutex::new(None),
+        }
+    }
+
+    pub fn options(&self) -> &MinerOptions {
+        &self.options
+    }
+
+    /// Seals `header` in place, writing `mix_digest` and `nonce`. Blocks
+    /// until a nonce is found, the difficulty must already be set.
+    pub fn seal(&self, header: &mut Header) {
+        let pow_hash = header.bare_hash();
+        let boundary = ethash::difficulty_to_boundary(&header.difficulty);
+        let hasher = match self.options.pow_mode {
+            PowMode::Light => Hasher::Light(self.manager.light(header.number)),
+            PowMode::Full => Hasher::Full(self.full(header.number)),
+        };
+
+        let threads = self.options.threads.max(1) as u64;
+        let found = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+        let workers: Vec<_> = (0..threads)
+            .map(|start| {
+                let hasher = hasher.clone();
+                let found = found.clone();
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    let mut nonce = start;
+                    while !found.load(Ordering::Relaxed) {
+                        let pow = hasher.compute(&pow_hash.0, nonce);
+                        if H256::from(pow.value) <= boundary {
+                            found.store(true, Ordering::Relaxed);
+                            let _ = tx.send((nonce, pow));
+                            return;
+                        }
+                        nonce = nonce.wrapping_add(threads);
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let (nonce, pow) = rx.recv().expect("workers search until a nonce is found");
+        found.store(true, Ordering::Relaxed);
+        for worker in workers {
+            worker.join().expect("miner thread panicked");
+        }
+        header.set_seal(H256::from(pow.mix_hash), nonce);
+    }
+
+    /// The full dataset for the epoch of `block_number`, only the latest is kept.
+    fn full(&self, block_number: u64) -> Arc<Full> {
+        let epoch = ethash::epoch(block_number);
+        let mut full = self.full.lock().expect("miner dataset lock poisoned");
+        match *full {
+            Some(ref dataset) if dataset.epoch() == epoch => return dataset.clone(),
+            _ => {}
+        }
+        let dataset = Arc::new(Full::new(&self.manager.light(block_number)));
+        *full = Some(dataset.clone());
+        dataset
+    }
+}
+
+#[derive(Clone)]
+enum Hasher {
+    Light(Arc<ethash::Light>),
+    Full(Arc<Full>),
+}
+
+impl Hasher {
+    fn compute(&self, pow_hash: &[u8; 32], nonce: u64) -> ProofOfWork {
+        match *self {
+            Hasher::Light(ref light) => light.compute(pow_hash, nonce),
+            Hasher::Full(ref full) => full.compute(pow_hash, nonce),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethereum_types::U256;
+    use header::Header;
+    use pow::Ethash;
+    use verification::SealVerifier;
+    use super::{Miner, MinerOptions, PowMode};
+
+    fn header(difficulty: u64) -> Header {
+        Header {
+            number: 1,
+            timestamp: 1,
+            difficulty: U256::from(difficulty),
+            ..Header::default()
+        }
+    }
+
+    #[test]
+    fn test_dev_mining() {
+        let engine = Ethash::dev();
+        for mode in [PowMode::Light, PowMode::Full].iter() {
+            let miner = Miner::new(
+                engine.manager().clone(),
+                MinerOptions {
+                    threads: 4,
+                    pow_mode: *mode,
+                },
+            );
+            let mut header = header(256);
+            miner.seal(&mut header);
+            assert_eq!(header.nonce.len(), 8);
+            assert_eq!(engine.verify_seal(&header), Ok(()));
+
+            header.set_seal(header.mix_digest, header.nonce() + 1);
+            assert!(engine.verify_seal(&header).is_err());
+        }
+    }
+}
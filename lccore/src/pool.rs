@@ -0,0 +1,52 @@
+use ethereum_types::H256;
+use error::TransactionError;
+use schedule::Schedule;
+use transaction::{SignedTransaction, UnverifiedTransaction};
+
+/// Verified transactions waiting to be included in a block, in arrival order.
+pub struct TransactionPool {
+    schedule: Schedule,
+    chain_id: Option<u64>,
+    pending: Vec<SignedTransaction>,
+}
+
+impl TransactionPool {
+    pub fn new(schedule: Schedule, chain_id: Option<u64>) -> Self {
+        TransactionPool {
+            schedule,
+            chain_id,
+            pending: vec![],
+        }
+    }
+
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    /// Runs the stateless checks and recovers the sender.
+    pub fn import(&mut self, transaction: UnverifiedTransaction) -> Result<H256, TransactionError> {
+        let hash = transaction.hash();
+        if self.pending.iter().any(|tx| tx.hash() == hash) {
+            return Err(TransactionError::AlreadyImported);
+        }
+        transaction.verify_basic(&self.schedule)?;
+        self.pending.push(transaction.verify(self.chain_id)?);
+        Ok(hash)
+    }
+
+    pub fn pending(&self) -> &[SignedTransaction] {
+        &self.pending
+    }
+
+    pub fn remove(&mut self, hashes: &[H256]) {
+        self.pending.retain(|tx| !hashes.contains(&tx.hash()));
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
@@ -0,0 +1,172 @@
+//! Ethash seal verification.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use ethash::{self, EthashManager};
+use ethereum_types::H256;
+use difficulty::DifficultyParams;
+use engines::{Engine, Seal};
+use error::BlockError;
+use header::Header;
+use miner::{Miner, MinerOptions};
+use verification::SealVerifier;
+
+const NONCE_LENGTH: usize = 8;
+
+/// Checks ethash seals, the mix digest against hashimoto-light of the bare
+/// hash and nonce, and the final hash against the difficulty boundary.
+pub struct Ethash {
+    manager: Arc<EthashManager>,
+    difficulty: DifficultyParams,
+}
+
+impl Ethash {
+    /// Light caches are kept under `cache_dir` when given.
+    pub fn new(cache_dir: Option<PathBuf>, difficulty: DifficultyParams) -> Self {
+        Ethash::with_manager(Arc::new(EthashManager::new(cache_dir)), difficulty)
+    }
+
+    /// Dev chains: tiny ethash caches and difficulty one, see
+    /// `EthashManager::dev` and `DifficultyParams::dev`.
+    pub fn dev() -> Self {
+        Ethash::with_manager(Arc::new(EthashManager::dev()), DifficultyParams::dev())
+    }
+
+    pub fn with_manager(manager: Arc<EthashManager>, difficulty: DifficultyParams) -> Self {
+        Ethash { manager, difficulty }
+    }
+
+    pub fn manager(&self) -> &Arc<EthashManager> {
+        &self.manager
+    }
+
+    pub fn difficulty_params(&self) -> &DifficultyParams {
+        &self.difficulty
+    }
+}
+
+impl SealVerifier for Ethash {
+    fn verify_seal(&self, header: &Header) -> Result<(), BlockError> {
+        if header.nonce.len() != NONCE_LENGTH {
+            return Err(BlockError::InvalidNonceLength {
+                expected: NONCE_LENGTH,
+                got: header.nonce.len(),
+            });
+        }
+        if header.difficulty < self.difficulty.minimum_difficulty {
+            return Err(BlockError::InvalidDifficulty {
+                expected: self.difficulty.minimum_difficulty,
+                got: header.difficulty,
+            });
+        }
+
+        let pow_hash = header.bare_hash();
+        let boundary = ethash::difficulty_to_boundary(&header.difficulty);
+        // cheap check against the claimed mix first, most bad seals stop here
+        let claimed = H256::from(ethash::quick_get_difficulty(
+            &pow_hash.0,
+            header.nonce(),
+            &header.mix_digest.0,
+        ));
+        if claimed > boundary {
+            return Err(BlockError::InvalidProofOfWork {
+                boundary,
+                got: claimed,
+            });
+        }
+
+        let pow = self.manager.compute_light(header.number, &pow_hash, header.nonce());
+        let mix_digest = H256::from(pow.mix_hash);
+        if mix_digest != header.mix_digest {
+            return Err(BlockError::MismatchedMixDigest {
+                expected: mix_digest,
+                got: header.mix_digest,
+            });
+        }
+        Ok(())
+    }
+
+    fn verify_seal_family(&self, header: &Header, parent: &Header) -> Result<(), BlockError> {
+        self.difficulty.verify_difficulty(header, parent)
+    }
+}
+
+impl Engine for Ethash {
+    fn name(&self) -> &str {
+        "Ethash"
+    }
+
+    fn prepare(&self, header: &mut Header, parent: &Header) -> Result<(), BlockError> {
+        header.difficulty = self.difficulty.calculate_difficulty(parent, header.timestamp)?;
+        Ok(())
+    }
+
+    fn seal(&self, header: &mut Header, _parent: &Header) -> Seal {
+        Miner::new(self.manager.clone(), MinerOptions::default()).seal(header);
+        Seal::Sealed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{BigEndian, ByteOrder};
+    use ethereum_types::{H256, U256};
+    use difficulty::DifficultyParams;
+    use error::BlockError;
+    use header::Header;
+    use verification::SealVerifier;
+    use super::Ethash;
+
+    fn engine() -> Ethash {
+        let mut params = DifficultyParams::mainnet();
+        params.minimum_difficulty = U256::one();
+        Ethash::new(None, params)
+    }
+
+    fn header(difficulty: u64) -> Header {
+        let mut nonce = vec![0; 8];
+        BigEndian::write_u64(&mut nonce, 0x42);
+        Header {
+            number: 1,
+            timestamp: 1,
+            difficulty: U256::from(difficulty),
+            nonce,
+            ..Header::default()
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_seal_before_hashimoto() {
+        let engine = engine();
+        let mut header = header(1 << 40);
+        assert!(matches!(
+            engine.verify_seal(&header),
+            Err(BlockError::InvalidProofOfWork { .. })
+        ));
+
+        header.nonce = vec![0; 4];
+        assert_eq!(
+            engine.verify_seal(&header),
+            Err(BlockError::InvalidNonceLength { expected: 8, got: 4 })
+        );
+    }
+
+    #[test]
+    fn test_verify_mix_digest() {
+        let engine = engine();
+        // difficulty one accepts any final hash, so only the mix matters
+        let mut header = header(1);
+        let pow = engine.manager().compute_light(1, &header.bare_hash(), header.nonce());
+        header.mix_digest = H256::from(pow.mix_hash);
+        assert_eq!(engine.verify_seal(&header), Ok(()));
+
+        header.mix_digest = H256::from(1);
+        assert_eq!(
+            engine.verify_seal(&header),
+            Err(BlockError::MismatchedMixDigest {
+                expected: H256::from(pow.mix_hash),
+                got: H256::from(1),
+            })
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! Builds, seals and keeps blocks on top of a base block with whatever
+//! engine it is given, enough to drive a dev chain or a test harness.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use ethereum_types::{H256, U256};
+use rlp;
+use trie::ordered_trie_root;
+use block::Block;
+use engines::{Engine, Seal};
+use error::{BlockError, ProducerError, TransactionError};
+use header::Header;
+use pool::TransactionPool;
+use receipt::Receipt;
+use transaction::UnverifiedTransaction;
+use verification::VerificationParams;
+
+pub struct BlockProducer {
+    engine: Arc<dyn Engine>,
+    params: VerificationParams,
+    pool: TransactionPool,
+    blocks: Vec<Block>,
+    receipts: Vec<Vec<Receipt>>,
+    /// Transaction hash to block number and index in the block.
+    locations: HashMap<H256, (u64, usize)>,
+}
+
+impl BlockProducer {
+    /// Builds on `base`, the genesis block or the best block of a stored
+    /// chain. Blocks before it are not kept.
+    pub fn new(engine: Arc<dyn Engine>, params: VerificationParams, pool: TransactionPool, base: Block) -> Self {
+        BlockProducer {
+            engine,
+            params,
+            pool,
+            blocks: vec![base],
+            receipts: vec![vec![]],
+            locations: HashMap::new(),
+        }
+    }
+
+    /// Adds a transaction to the pool. Engines that seal on transaction
+    /// arrival seal it into a block straight away. If that fails the error
+    /// is returned as `ProducerError::Block` and the transaction stays
+    /// pending for `seal_pending`.
+    ///
+    /// Transactions already sealed into a block are rejected as
+    /// `TransactionError::AlreadyImported`.
+    pub fn submit_transaction(&mut self, transaction: UnverifiedTransaction) -> Result<H256, ProducerError> {
+        if self.locations.contains_key(&transaction.hash()) {
+            return Err(TransactionError::AlreadyImported.into());
+        }
+        let hash = self.pool.import(transaction)?;
+        if self.engine.seals_on_transaction() {
+            self.seal_pending()?;
+        }
+        Ok(hash)
+    }
+
+    /// Builds a block from pending transactions on top of the best block and
+    /// seals it. `Ok(None)` when the engine can't seal right now.
+    ///
+    /// There is no execution yet, each transaction uses its intrinsic gas
+    /// and succeeds.
+    pub fn seal_pending(&mut self) -> Result<Option<&Block>, BlockError> {
+        let parent = self.best_block().header.clone();
+        let mut header = Header {
+            parent_hash: parent.hash(),
+            number: parent.number.checked_add(1).ok_or(BlockError::NumberOverflow)?,
+            gas_limit: parent.gas_limit,
+            ..Header::default()
+        };
+
+        let mut transactions = vec![];
+        let mut receipts = vec![];
+        let mut gas_used = U256::zero();
+        for tx in self.pool.pending() {
+            let gas = tx.intrinsic_gas(self.pool.schedule());
+            if gas_used + gas > header.gas_limit {
+                break;
+            }
+            gas_used = gas_used + gas;
+            transactions.push(UnverifiedTransaction::clone(tx));
+            receipts.push(Receipt {
+                status: 1,
+                cumulative_gas_used: gas_used,
+                ..Receipt::default()
+            });
+        }
+        header.gas_used = gas_used;
+        header.receipts_root = ordered_trie_root(receipts.iter().map(rlp::encode));
+        let mut block = Block {
+            header,
+            transactions,
+            uncles: vec![],
+        };
+        block.header.transactions_root = block.compute_transactions_root();
+
+        self.engine.prepare(&mut block.header, &parent)?;
+        if self.engine.seal(&mut block.header, &parent) == Seal::None {
+            return Ok(None);
+        }
+        self.engine.verify_header(&block.header, Some(&parent), &self.params)?;
+
+        let hashes: Vec<H256> = block.transactions.iter().map(|tx| tx.hash()).collect();
+        for (index, hash) in hashes.iter().enumerate() {
+            self.locations.insert(*hash, (block.header.number, index));
+        }
+        self.pool.remove(&hashes);
+        self.blocks.push(block);
+        self.receipts.push(receipts);
+        Ok(self.blocks.last())
+    }
+
+    /// Drops every block but the best one, with their receipts, once the
+    /// caller has stored them elsewhere. Their transactions are no longer
+    /// rejected as `AlreadyImported` by `submit_transaction`.
+    pub fn prune(&mut self) {
+        let keep = self.blocks.len() - 1;
+        self.blocks.drain(..keep);
+        self.receipts.drain(..keep);
+        let best = self.blocks[0].header.number;
+        self.locations.retain(|_, &mut (number, _)| number == best);
+    }
+
+    pub fn best_block(&self) -> &Block {
+        self.blocks.last().expect("there is always a base block")
+    }
+
+    /// Position of block `number` in `blocks` and `receipts`.
+    fn index(&self, number: u64) -> Option<usize> {
+        number.checked_sub(self.blocks[0].header.number).map(|index| index as usize)
+    }
+
+    pub fn block(&self, number: u64) -> Option<&Block> {
+        self.index(number).and_then(|index| self.blocks.get(index))
+    }
+
+    pub fn pool(&self) -> &TransactionPool {
+        &self.pool
+    }
+
+    /// Receipt of an included transaction.
+    pub fn receipt(&self, hash: &H256) -> Option<&Receipt> {
+        let &(number, index) = self.locations.get(hash)?;
+        self.index(number).map(|block| &self.receipts[block][index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use ethereum_types::U256;
+    use keccak_hash::KECCAK_NULL_RLP;
+    use rlp;
+    use trie::ordered_trie_root;
+    use block::Block;
+    use crypto::Secret;
+    use engines::{Engine, InstantSeal, Seal};
+    use error::{BlockError, ProducerError, TransactionError};
+    use header::Header;
+    use pool::TransactionPool;
+    use schedule::Schedule;
+    use transaction::{Recipient, Transaction, UnverifiedTransaction};
+    use verification::{SealVerifier, VerificationParams};
+    use super::BlockProducer;
+
+    fn producer() -> BlockProducer {
+        producer_with(Arc::new(InstantSeal))
+    }
+
+    fn producer_with(engine: Arc<dyn Engine>) -> BlockProducer {
+        producer_on(engine, 0)
+    }
+
+    fn producer_on(engine: Arc<dyn Engine>, number: u64) -> BlockProducer {
+        let base = Block {
+            header: Header {
+                number,
+                timestamp: 1000,
+                gas_limit: U256::from(8_000_000),
+                nonce: vec![0; 8],
+                ..Header::default()
+            },
+            transactions: vec![],
+            uncles: vec![],
+        };
+        BlockProducer::new(
+            engine,
+            VerificationParams::default(),
+            TransactionPool::new(Schedule::default(), Some(1)),
+            base,
+        )
+    }
+
+    fn transfer(nonce: u64) -> UnverifiedTransaction {
+        let mut tx = Transaction::new();
+        tx.nonce = nonce;
+        tx.gas_limit = U256::from(21000);
+        tx.recipient = Recipient::call(5.into());
+        tx.amount = U256::from(1);
+        tx.sign(&Secret::from(1), Some(1)).unwrap().into()
+    }
+
+    #[test]
+    fn test_instant_seal_on_submit() {
+        let mut producer = producer();
+        let hash = producer.submit_transaction(transfer(0)).unwrap();
+        assert_eq!(producer.best_block().header.number, 1);
+        assert_eq!(producer.best_block().header.timestamp, 1001);
+        assert_eq!(producer.best_block().transactions[0].hash(), hash);
+        let header = &producer.best_block().header;
+        assert_eq!(header.transactions_root, producer.best_block().compute_transactions_root());
+        assert!(header.transactions_root != KECCAK_NULL_RLP);
+        assert_eq!(header.receipts_root, ordered_trie_root(vec![rlp::encode(producer.receipt(&hash).unwrap())]));
+        assert!(producer.pool().is_empty());
+        let receipt = producer.receipt(&hash).unwrap();
+        assert_eq!(receipt.status, 1);
+        assert_eq!(receipt.cumulative_gas_used, U256::from(21000));
+
+        producer.submit_transaction(transfer(1)).unwrap();
+        assert_eq!(producer.best_block().header.number, 2);
+        assert_eq!(producer.best_block().header.timestamp, 1002);
+        assert_eq!(
+            producer.block(2).unwrap().header.parent_hash,
+            producer.block(1).unwrap().header.hash()
+        );
+    }
+
+    #[test]
+    fn test_seal_error_on_submit() {
+        struct Broken;
+        impl SealVerifier for Broken {}
+        impl Engine for Broken {
+            fn name(&self) -> &str {
+                "Broken"
+            }
+
+            fn prepare(&self, _: &mut Header, _: &Header) -> Result<(), BlockError> {
+                Err(BlockError::ZeroTimestamp)
+            }
+
+            fn seal(&self, _: &mut Header, _: &Header) -> Seal {
+                Seal::None
+            }
+
+            fn seals_on_transaction(&self) -> bool {
+                true
+            }
+        }
+
+        let mut producer = producer_with(Arc::new(Broken));
+        assert_eq!(
+            producer.submit_transaction(transfer(0)),
+            Err(ProducerError::Block(BlockError::ZeroTimestamp))
+        );
+        assert_eq!(producer.best_block().header.number, 0);
+        assert_eq!(producer.pool().len(), 1);
+        assert_eq!(
+            producer.submit_transaction(transfer(0)),
+            Err(ProducerError::Transaction(TransactionError::AlreadyImported))
+        );
+    }
+
+    #[test]
+    fn test_build_on_stored_block() {
+        let mut producer = producer_on(Arc::new(InstantSeal), 5);
+        let hash = producer.submit_transaction(transfer(0)).unwrap();
+        assert_eq!(producer.best_block().header.number, 6);
+        assert_eq!(producer.block(6).unwrap().transactions[0].hash(), hash);
+        assert_eq!(producer.block(5).unwrap().header.number, 5);
+        assert_eq!(producer.block(4), None);
+        assert_eq!(producer.receipt(&hash).unwrap().cumulative_gas_used, U256::from(21000));
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut producer = producer();
+        let first = producer.submit_transaction(transfer(0)).unwrap();
+        let second = producer.submit_transaction(transfer(1)).unwrap();
+        producer.prune();
+        assert_eq!(producer.best_block().header.number, 2);
+        assert_eq!(producer.block(1), None);
+        assert_eq!(producer.receipt(&first), None);
+        assert_eq!(producer.receipt(&second).unwrap().cumulative_gas_used, U256::from(21000));
+
+        producer.submit_transaction(transfer(2)).unwrap();
+        assert_eq!(producer.block(3).unwrap().header.parent_hash, producer.block(2).unwrap().header.hash());
+    }
+
+    #[test]
+    fn test_sealed_transaction_is_not_resubmitted() {
+        let mut producer = producer();
+        let hash = producer.submit_transaction(transfer(0)).unwrap();
+        assert_eq!(
+            producer.submit_transaction(transfer(0)),
+            Err(ProducerError::Transaction(TransactionError::AlreadyImported))
+        );
+        assert_eq!(producer.best_block().header.number, 1);
+        assert_eq!(producer.receipt(&hash).unwrap().cumulative_gas_used, U256::from(21000));
+    }
+
+    #[test]
+    fn test_timestamp_overflow() {
+        let mut producer = producer();
+        producer.blocks[0].header.timestamp = u64::MAX;
+        assert_eq!(
+            producer.submit_transaction(transfer(0)),
+            Err(ProducerError::Block(BlockError::TimestampOverflow))
+        );
+    }
+
+    #[test]
+    fn test_deterministic_chain() {
+        let mut a = producer();
+        let mut b = producer();
+        for nonce in 0..3 {
+            a.submit_transaction(transfer(nonce)).unwrap();
+            b.submit_transaction(transfer(nonce)).unwrap();
+        }
+        assert_eq!(a.best_block(), b.best_block());
+        assert_eq!(a.best_block().header.number, 3);
+    }
+
+    #[test]
+    fn test_pool_rejects_duplicates() {
+        let mut pool = TransactionPool::new(Schedule::default(), Some(1));
+        pool.import(transfer(0)).unwrap();
+        assert_eq!(pool.import(transfer(0)), Err(TransactionError::AlreadyImported));
+        assert_eq!(pool.len(), 1);
+    }
+}
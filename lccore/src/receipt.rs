@@ -0,0 +1,90 @@
+use bytes::Bytes;
+use ethereum_types::{Address, Bloom, H256, U256};
+use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogEntry {
+    pub address: Address,
+    pub topics: Vec<H256>,
+    pub data: Bytes,
+}
+
+impl Encodable for LogEntry {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3);
+        s.append(&self.address);
+        s.append_list(&self.topics);
+        s.append(&self.data);
+    }
+}
+
+impl Decodable for LogEntry {
+    fn decode(rlp: &UntrustedRlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(LogEntry {
+            address: rlp.val_at(0)?,
+            topics: rlp.list_at(1)?,
+            data: rlp.val_at(2)?,
+        })
+    }
+}
+
+/// Outcome of a transaction in post-Byzantium form: status code, gas used
+/// by the block up to and including it, and the logs it emitted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Receipt {
+    /// 1 on success, 0 on failure.
+    pub status: u8,
+    pub cumulative_gas_used: U256,
+    pub log_bloom: Bloom,
+    pub logs: Vec<LogEntry>,
+}
+
+impl Encodable for Receipt {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4);
+        s.append(&self.status);
+        s.append(&self.cumulative_gas_used);
+        s.append(&self.log_bloom);
+        s.append_list(&self.logs);
+    }
+}
+
+impl Decodable for Receipt {
+    fn decode(rlp: &UntrustedRlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(Receipt {
+            status: rlp.val_at(0)?,
+            cumulative_gas_used: rlp.val_at(1)?,
+            log_bloom: rlp.val_at(2)?,
+            logs: rlp.list_at(3)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethereum_types::U256;
+    use rlp;
+    use super::{LogEntry, Receipt};
+
+    #[test]
+    fn test_receipt_rlp() {
+        let receipt = Receipt {
+            status: 1,
+            cumulative_gas_used: U256::from(21000),
+            logs: vec![LogEntry {
+                address: 5.into(),
+                topics: vec![1.into(), 2.into()],
+                data: vec![0xff],
+            }],
+            ..Receipt::default()
+        };
+        let decoded: Receipt = rlp::decode(&rlp::encode(&receipt));
+        assert_eq!(decoded, receipt);
+    }
+}
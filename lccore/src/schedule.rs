@@ -0,0 +1,37 @@
+/// Gas costs charged before any code is executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    pub tx_gas: u64,
+    pub tx_create_gas: u64,
+    pub tx_data_zero_gas: u64,
+    pub tx_data_non_zero_gas: u64,
+    pub access_list_address_gas: u64,
+    pub access_list_storage_key_gas: u64,
+}
+
+impl Schedule {
+    pub fn homestead() -> Self {
+        Schedule {
+            tx_gas: 21000,
+            tx_create_gas: 53000,
+            tx_data_zero_gas: 4,
+            tx_data_non_zero_gas: 68,
+            access_list_address_gas: 2400,
+            access_list_storage_key_gas: 1900,
+        }
+    }
+
+    /// EIP-2028 makes non-zero payload bytes cheaper.
+    pub fn istanbul() -> Self {
+        Schedule {
+            tx_data_non_zero_gas: 16,
+            ..Schedule::homestead()
+        }
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule::istanbul()
+    }
+}
@@ -0,0 +1,423 @@
+//! World state: accounts in a secure trie keyed by `keccak(address)`, each
+//! with a storage trie keyed by `keccak(slot)`. Changes are cached per
+//! account and only written to the tries on `commit`.
+
+use std::collections::HashMap;
+use bytes::Bytes;
+use ethereum_types::{Address, H256, U256};
+use keccak_hash::{keccak, KECCAK_NULL_RLP};
+use rlp::{self, UntrustedRlp};
+use trie::{HashDB, TrieDB, TrieDBMut, TrieError};
+use account::Account;
+use error::StateError;
+
+#[derive(Debug, Clone, Default)]
+struct CachedAccount {
+    account: Account,
+    /// New code not yet written to the database.
+    code: Option<Bytes>,
+    storage: HashMap<H256, H256>,
+    dirty: bool,
+}
+
+fn decode_account(bytes: &[u8]) -> Result<Account, TrieError> {
+    UntrustedRlp::new(bytes)
+        .as_val()
+        .map_err(|e| TrieError::CorruptNode(keccak(bytes), e))
+}
+
+pub struct State<DB: HashDB> {
+    db: DB,
+    root: H256,
+    cache: HashMap<Address, CachedAccount>,
+    /// For each open checkpoint, the cache entries as they were before the
+    /// first change made after it, `None` when the account wasn't cached.
+    checkpoints: Vec<HashMap<Address, Option<CachedAccount>>>,
+}
+
+impl<DB: HashDB> State<DB> {
+    /// An empty state.
+    pub fn new(db: DB) -> Self {
+        State {
+            db,
+            root: KECCAK_NULL_RLP,
+            cache: HashMap::new(),
+            checkpoints: vec![],
+        }
+    }
+
+    pub fn from_existing(db: DB, root: H256) -> Result<Self, StateError> {
+        TrieDB::new(&db, root)?;
+        Ok(State {
+            root,
+            ..State::new(db)
+        })
+    }
+
+    /// Root as of the last commit.
+    pub fn root(&self) -> &H256 {
+        &self.root
+    }
+
+    pub fn db(&self) -> &DB {
+        &self.db
+    }
+
+    pub fn into_db(self) -> DB {
+        self.db
+    }
+
+    /// The account as committed, ignoring cached changes.
+    fn committed_account(&self, address: &Address) -> Result<Option<Account>, TrieError> {
+        let trie = TrieDB::new(&self.db, self.root)?;
+        match trie.get(&keccak(address))? {
+            Some(bytes) => decode_account(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn account(&self, address: &Address) -> Result<Option<Account>, StateError> {
+        match self.cache.get(address) {
+            Some(cached) => Ok(Some(cached.account.clone())),
+            None => Ok(self.committed_account(address)?),
+        }
+    }
+
+    pub fn exists(&self, address: &Address) -> Result<bool, StateError> {
+        self.account(address).map(|account| account.is_some())
+    }
+
+    pub fn balance(&self, address: &Address) -> Result<U256, StateError> {
+        Ok(self.account(address)?.map_or_else(U256::zero, |account| account.balance))
+    }
+
+    pub fn nonce(&self, address: &Address) -> Result<u64, StateError> {
+        Ok(self.account(address)?.map_or(0, |account| account.nonce))
+    }
+
+    pub fn code(&self, address: &Address) -> Result<Option<Bytes>, StateError> {
+        if let Some(code) = self.cache.get(address).and_then(|cached| cached.code.clone()) {
+            return Ok(Some(code));
+        }
+        match self.account(address)? {
+            Some(ref account) if account.has_code() => Ok(Some(
+                self.db
+                    .get(&account.code_hash)
+                    .ok_or(TrieError::IncompleteDatabase(account.code_hash))?,
+            )),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn storage_at(&self, address: &Address, key: &H256) -> Result<H256, StateError> {
+        if let Some(value) = self.cache.get(address).and_then(|cached| cached.storage.get(key)) {
+            return Ok(*value);
+        }
+        let account = match self.account(address)? {
+            Some(account) => account,
+            None => return Ok(H256::zero()),
+        };
+        let trie = TrieDB::new(&self.db, account.storage_root)?;
+        match trie.get(&keccak(key))? {
+            Some(bytes) => {
+                let value: U256 = UntrustedRlp::new(&bytes)
+                    .as_val()
+                    .map_err(|e| TrieError::CorruptNode(keccak(&bytes), e))?;
+                Ok(H256::from(value))
+            }
+            None => Ok(H256::zero()),
+        }
+    }
+
+    /// The cached account to change, created empty when it doesn't exist.
+    fn require(&mut self, address: &Address) -> Result<&mut CachedAccount, StateError> {
+        if let Some(checkpoint) = self.checkpoints.last_mut() {
+            if !checkpoint.contains_key(address) {
+                checkpoint.insert(*address, self.cache.get(address).cloned());
+            }
+        }
+        if !self.cache.contains_key(address) {
+            let account = self.committed_account(address)?;
+            self.cache.insert(
+                *address,
+                CachedAccount {
+                    account: account.unwrap_or_default(),
+                    ..CachedAccount::default()
+                },
+            );
+        }
+        Ok(self.cache.get_mut(address).expect("inserted above"))
+    }
+
+    pub fn add_balance(&mut self, address: &Address, amount: &U256) -> Result<(), StateError> {
+        let cached = self.require(address)?;
+        let (balance, overflow) = cached.account.balance.overflowing_add(*amount);
+        if overflow {
+            return Err(StateError::BalanceOverflow {
+                balance: cached.account.balance,
+                amount: *amount,
+            });
+        }
+        cached.account.balance = balance;
+        cached.dirty = true;
+        Ok(())
+    }
+
+    pub fn sub_balance(&mut self, address: &Address, amount: &U256) -> Result<(), StateError> {
+        let balance = self.balance(address)?;
+        if balance < *amount {
+            return Err(StateError::InsufficientBalance {
+                required: *amount,
+                got: balance,
+            });
+        }
+        let cached = self.require(address)?;
+        cached.account.balance = balance - *amount;
+        cached.dirty = true;
+        Ok(())
+    }
+
+    /// Moves `amount` from `from` to `to`, leaving both untouched when `from`
+    /// can't cover it or `to` can't hold it.
+    pub fn transfer_balance(&mut self, from: &Address, to: &Address, amount: &U256) -> Result<(), StateError> {
+        if from != to {
+            let balance = self.balance(to)?;
+            if balance.overflowing_add(*amount).1 {
+                return Err(StateError::BalanceOverflow {
+                    balance,
+                    amount: *amount,
+                });
+            }
+        }
+        self.sub_balance(from, amount)?;
+        self.add_balance(to, amount)
+    }
+
+    pub fn inc_nonce(&mut self, address: &Address) -> Result<(), StateError> {
+        let cached = self.require(address)?;
+        cached.account.nonce += 1;
+        cached.dirty = true;
+        Ok(())
+    }
+
+    pub fn set_code(&mut self, address: &Address, code: Bytes) -> Result<(), StateError> {
+        let cached = self.require(address)?;
+        cached.account.code_hash = keccak(&code);
+        cached.code = if code.is_empty() { None } else { Some(code) };
+        cached.dirty = true;
+        Ok(())
+    }
+
+    pub fn set_storage(&mut self, address: &Address, key: H256, value: H256) -> Result<(), StateError> {
+        let cached = self.require(address)?;
+        cached.storage.insert(key, value);
+        cached.dirty = true;
+        Ok(())
+    }
+
+    /// Opens a checkpoint to revert to or discard. Checkpoints nest.
+    pub fn checkpoint(&mut self) {
+        self.checkpoints.push(HashMap::new());
+    }
+
+    /// Undoes every change made since the last checkpoint and closes it.
+    pub fn revert_to_checkpoint(&mut self) {
+        let checkpoint = self.checkpoints.pop().expect("revert without a checkpoint");
+        for (address, previous) in checkpoint {
+            match previous {
+                Some(cached) => self.cache.insert(address, cached),
+                None => self.cache.remove(&address),
+            };
+        }
+    }
+
+    /// Keeps the changes made since the last checkpoint and closes it, they
+    /// are still undone by reverting an outer checkpoint.
+    pub fn discard_checkpoint(&mut self) {
+        let checkpoint = self.checkpoints.pop().expect("discard without a checkpoint");
+        if let Some(outer) = self.checkpoints.last_mut() {
+            for (address, previous) in checkpoint {
+                outer.entry(address).or_insert(previous);
+            }
+        }
+    }
+
+    /// Writes cached changes to the tries and returns the new state root.
+    /// Open checkpoints are closed, the changes can't be reverted after this.
+    pub fn commit(&mut self) -> Result<H256, StateError> {
+        self.checkpoints.clear();
+        let mut addresses: Vec<Address> = self.cache
+            .iter()
+            .filter(|&(_, cached)| cached.dirty)
+            .map(|(address, _)| *address)
+            .collect();
+        addresses.sort();
+
+        for address in addresses {
+            let cached = self.cache.get_mut(&address).expect("address taken from the cache");
+            if let Some(code) = cached.code.take() {
+                self.db.emplace(cached.account.code_hash, code);
+            }
+            if !cached.storage.is_empty() {
+                let mut trie = TrieDBMut::from_existing(&mut self.db, cached.account.storage_root)?;
+                for (key, value) in cached.storage.drain() {
+                    if value.is_zero() {
+                        trie.remove(&keccak(key))?;
+                    } else {
+                        trie.insert(&keccak(key), &rlp::encode(&U256::from(&*value)))?;
+                    }
+                }
+                cached.account.storage_root = *trie.root();
+            }
+
+            let mut trie = TrieDBMut::from_existing(&mut self.db, self.root)?;
+            trie.insert(&keccak(address), &rlp::encode(&cached.account))?;
+            self.root = *trie.root();
+            cached.dirty = false;
+        }
+        Ok(self.root)
+    }
+
+    /// Proof of the account at `address` against the committed root, see
+    /// `trie::verify_proof`.
+    pub fn account_proof(&self, address: &Address) -> Result<Vec<Bytes>, StateError> {
+        Ok(TrieDB::new(&self.db, self.root)?.get_proof(&keccak(address))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethereum_types::{Address, H256, U256};
+    use keccak_hash::{keccak, KECCAK_NULL_RLP};
+    use rlp;
+    use rustc_hex::FromHex;
+    use trie::{verify_proof, MemoryDB};
+    use account::Account;
+    use error::StateError;
+    use super::State;
+
+    fn address(n: u64) -> Address {
+        Address::from(H256::from(n))
+    }
+
+    #[test]
+    fn test_transfer_and_commit() {
+        let mut state = State::new(MemoryDB::new());
+        assert_eq!(state.commit().unwrap(), KECCAK_NULL_RLP);
+
+        state.add_balance(&address(1), &U256::from(100)).unwrap();
+        state.inc_nonce(&address(1)).unwrap();
+        assert_eq!(
+            state.transfer_balance(&address(1), &address(2), &U256::from(101)),
+            Err(StateError::InsufficientBalance {
+                required: U256::from(101),
+                got: U256::from(100),
+            })
+        );
+        state.transfer_balance(&address(1), &address(2), &U256::from(30)).unwrap();
+        let root = state.commit().unwrap();
+
+        let state = State::from_existing(state.into_db(), root).unwrap();
+        assert_eq!(state.balance(&address(1)).unwrap(), U256::from(70));
+        assert_eq!(state.nonce(&address(1)).unwrap(), 1);
+        assert_eq!(state.balance(&address(2)).unwrap(), U256::from(30));
+        assert!(!state.exists(&address(3)).unwrap());
+
+        let proof = state.account_proof(&address(2)).unwrap();
+        let account = verify_proof(&root, &keccak(address(2)), &proof).unwrap().unwrap();
+        assert_eq!(rlp::decode::<Account>(&account), Account::new(0, U256::from(30)));
+    }
+
+    #[test]
+    fn test_balance_overflow() {
+        let mut state = State::new(MemoryDB::new());
+        state.add_balance(&address(1), &U256::max_value()).unwrap();
+        state.add_balance(&address(2), &U256::from(1)).unwrap();
+        assert_eq!(
+            state.add_balance(&address(1), &U256::from(1)),
+            Err(StateError::BalanceOverflow {
+                balance: U256::max_value(),
+                amount: U256::from(1),
+            })
+        );
+        assert_eq!(
+            state.transfer_balance(&address(2), &address(1), &U256::from(1)),
+            Err(StateError::BalanceOverflow {
+                balance: U256::max_value(),
+                amount: U256::from(1),
+            })
+        );
+        assert_eq!(state.balance(&address(1)).unwrap(), U256::max_value());
+        assert_eq!(state.balance(&address(2)).unwrap(), U256::from(1));
+    }
+
+    #[test]
+    fn test_goerli_genesis_root() {
+        let mut state = State::new(MemoryDB::new());
+        // the precompile addresses 0x00 to 0xff each hold one wei
+        for n in 0..256 {
+            state.add_balance(&address(n), &U256::one()).unwrap();
+        }
+        let alloc = [
+            ("4c2ae482593505f0163cdefc073e81c63cda4107", "152d02c7e14af6800000"),
+            ("a8e8f14732658e4b51e8711931053a8a69baf2b1", "152d02c7e14af6800000"),
+            ("d9a5179f091d85051d3c982785efd1455cec8699", "084595161401484a000000"),
+            ("e0a2bd4258d2768837baa26a28fe71dc079f84c7", "4a47e3c12448f4ad000000"),
+        ];
+        for &(address, balance) in &alloc {
+            let address = Address::from(address.from_hex().unwrap().as_slice());
+            let balance = U256::from(balance.from_hex().unwrap().as_slice());
+            state.add_balance(&address, &balance).unwrap();
+        }
+        assert_eq!(
+            state.commit().unwrap(),
+            "5d6cded585e73c4e322c30c2f782a336316f17dd85a4863b9d838d2d4b8b3008".into()
+        );
+    }
+
+    #[test]
+    fn test_code_and_storage() {
+        let mut state = State::new(MemoryDB::new());
+        state.set_code(&address(1), vec![0x60, 0x00]).unwrap();
+        state.set_storage(&address(1), H256::from(1), H256::from(0xff)).unwrap();
+        state.set_storage(&address(1), H256::from(2), H256::from(0xee)).unwrap();
+        let root = state.commit().unwrap();
+        assert_eq!(state.code(&address(1)).unwrap(), Some(vec![0x60, 0x00]));
+
+        let mut state = State::from_existing(state.into_db(), root).unwrap();
+        assert_eq!(state.code(&address(1)).unwrap(), Some(vec![0x60, 0x00]));
+        assert_eq!(state.code(&address(2)).unwrap(), None);
+        assert_eq!(state.storage_at(&address(1), &H256::from(1)).unwrap(), H256::from(0xff));
+        assert_eq!(state.storage_at(&address(1), &H256::from(3)).unwrap(), H256::zero());
+
+        // clearing every slot brings back the empty storage root
+        state.set_storage(&address(1), H256::from(1), H256::zero()).unwrap();
+        state.set_storage(&address(1), H256::from(2), H256::zero()).unwrap();
+        state.commit().unwrap();
+        assert_eq!(state.account(&address(1)).unwrap().unwrap().storage_root, KECCAK_NULL_RLP);
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let mut state = State::new(MemoryDB::new());
+        state.add_balance(&address(1), &U256::from(10)).unwrap();
+        state.checkpoint();
+        state.add_balance(&address(1), &U256::from(5)).unwrap();
+        state.inc_nonce(&address(2)).unwrap();
+        state.checkpoint();
+        state.set_storage(&address(1), H256::from(1), H256::from(1)).unwrap();
+        state.discard_checkpoint();
+        assert_eq!(state.storage_at(&address(1), &H256::from(1)).unwrap(), H256::from(1));
+        state.revert_to_checkpoint();
+
+        assert_eq!(state.balance(&address(1)).unwrap(), U256::from(10));
+        assert_eq!(state.storage_at(&address(1), &H256::zero()).unwrap(), H256::zero());
+        assert_eq!(state.storage_at(&address(1), &H256::from(1)).unwrap(), H256::zero());
+        assert!(!state.exists(&address(2)).unwrap());
+
+        let root = state.commit().unwrap();
+        let mut expected = State::new(MemoryDB::new());
+        expected.add_balance(&address(1), &U256::from(10)).unwrap();
+        assert_eq!(expected.commit().unwrap(), root);
+    }
+}
@@ -1,10 +1,47 @@
-use ethereum_types::{Address, Bloom, H256, U256};
+use std::ops::Deref;
+use ethereum_types::{Address, H256, U256};
 use bytes::Bytes;
-use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
+use keccak_hash::keccak;
+use rlp::{self, Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
+use contract_address::contract_address;
+use crypto::{self, Public, Secret};
+use error::TransactionError;
+use schedule::Schedule;
+use typed_transaction::TypedTransaction;
 
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct Recipient(Option<Address>);
 
+impl Recipient {
+    pub fn create() -> Self {
+        Recipient(None)
+    }
+
+    pub fn call(address: Address) -> Self {
+        Recipient(Some(address))
+    }
+
+    pub fn is_create(&self) -> bool {
+        self.0.is_none()
+    }
+
+    pub fn address(&self) -> Option<&Address> {
+        self.0.as_ref()
+    }
+}
+
+impl From<Address> for Recipient {
+    fn from(address: Address) -> Self {
+        Recipient::call(address)
+    }
+}
+
+impl From<Option<Address>> for Recipient {
+    fn from(address: Option<Address>) -> Self {
+        Recipient(address)
+    }
+}
+
 impl Encodable for Recipient {
     fn rlp_append(&self, s: &mut RlpStream) {
         match self.0 {
@@ -43,7 +80,7 @@ impl Transaction {
             nonce: 0,
             gas_price: U256::default(),
             gas_limit: U256::default(),
-            recipient: Recipient(None),
+            recipient: Recipient::create(),
             amount: U256::default(),
             payload: vec![],
             v: U256::default(),
@@ -51,6 +88,71 @@ impl Transaction {
             s: U256::default(),
         }
     }
+
+    pub fn hash(&self) -> H256 {
+        keccak(rlp::encode(self))
+    }
+
+    pub fn signature_hash(&self, chain_id: Option<u64>) -> H256 {
+        let mut s = RlpStream::new();
+        self.stream_unsigned(&mut s, chain_id);
+        keccak(s.as_raw())
+    }
+
+    pub fn sign(
+        mut self,
+        secret: &Secret,
+        chain_id: Option<u64>,
+    ) -> Result<SignedTransaction, TransactionError> {
+        let signature = crypto::sign(secret, &self.signature_hash(chain_id))?;
+        self.v = U256::from(signature.v) + match chain_id {
+            Some(n) => U256::from(n) * U256::from(2) + U256::from(35),
+            None => U256::from(27),
+        };
+        self.r = U256::from(signature.r);
+        self.s = U256::from(signature.s);
+        SignedTransaction::new(TypedTransaction::Legacy(self).into())
+    }
+
+    fn stream_unsigned(&self, s: &mut RlpStream, chain_id: Option<u64>) {
+        s.begin_list(if chain_id.is_some() { 9 } else { 6 });
+        s.append(&self.nonce);
+        s.append(&self.gas_price);
+        s.append(&self.gas_limit);
+        s.append(&self.recipient);
+        s.append(&self.amount);
+        s.append(&self.payload);
+        if let Some(n) = chain_id {
+            s.append(&n);
+            s.append(&0u8);
+            s.append(&0u8);
+        }
+    }
+
+    /// The chain id this transaction is replay protected for (EIP-155),
+    /// `None` for unprotected transactions signed with `v` of 27 or 28.
+    pub fn chain_id(&self) -> Option<u64> {
+        if self.v < U256::from(35) {
+            return None;
+        }
+        let n = (self.v - U256::from(35)) / U256::from(2);
+        if n > U256::from(u64::MAX) {
+            None
+        } else {
+            Some(n.low_u64())
+        }
+    }
+
+    /// The signature recovery id (0 or 1) encoded in `v`, `None` if `v`
+    /// is neither an unprotected nor an EIP-155 value.
+    pub fn standard_v(&self) -> Option<u8> {
+        if self.v == U256::from(27) || self.v == U256::from(28) {
+            Some((self.v.low_u64() - 27) as u8)
+        } else {
+            self.chain_id()
+                .map(|n| (self.v - U256::from(n) * U256::from(2) - U256::from(35)).low_u64() as u8)
+        }
+    }
 }
 
 impl Encodable for Transaction {
@@ -88,12 +190,204 @@ impl Decodable for Transaction {
     }
 }
 
+/// A transaction decoded from the outside world whose signature has not
+/// been checked yet. The transaction hash is computed once on creation,
+/// so pools and indexes can key by `hash()` without re-encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnverifiedTransaction {
+    transaction: TypedTransaction,
+    hash: H256,
+}
+
+impl UnverifiedTransaction {
+    pub fn hash(&self) -> H256 {
+        self.hash
+    }
+
+    pub fn recover_public(&self) -> Result<Public, TransactionError> {
+        Ok(crypto::recover(
+            &self.transaction.signature()?,
+            &self.transaction.signature_hash(),
+        )?)
+    }
+
+    /// Rejects transactions replay protected for a chain other than
+    /// `chain_id`, unprotected transactions are valid on every chain.
+    pub fn check_chain_id(&self, chain_id: Option<u64>) -> Result<(), TransactionError> {
+        match self.transaction.chain_id() {
+            None => Ok(()),
+            Some(n) if Some(n) == chain_id => Ok(()),
+            got => Err(TransactionError::InvalidChainId {
+                expected: chain_id,
+                got,
+            }),
+        }
+    }
+
+    /// Checks that do not need any state: signature values in range with a
+    /// low `s`, enough gas to cover the intrinsic cost and, for dynamic fee
+    /// transactions, a priority fee not above the fee cap.
+    pub fn verify_basic(&self, schedule: &Schedule) -> Result<(), TransactionError> {
+        let (r, s) = (self.transaction.r(), self.transaction.s());
+        if !crypto::is_valid_signature_values(r, s) {
+            return Err(TransactionError::InvalidSignature(
+                crypto::Error::InvalidSignature,
+            ));
+        }
+        if !crypto::is_low_s(s) {
+            return Err(TransactionError::HighS);
+        }
+
+        let required = self.transaction.intrinsic_gas(schedule);
+        let got = *self.transaction.gas_limit();
+        if got < required {
+            return Err(TransactionError::NotEnoughBaseGas { required, got });
+        }
+
+        if let TypedTransaction::DynamicFee(ref tx) = self.transaction {
+            if tx.max_priority_fee_per_gas > tx.max_fee_per_gas {
+                return Err(TransactionError::TipAboveFeeCap {
+                    max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
+                    max_fee_per_gas: tx.max_fee_per_gas,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn verify(self, chain_id: Option<u64>) -> Result<SignedTransaction, TransactionError> {
+        self.check_chain_id(chain_id)?;
+        SignedTransaction::new(self)
+    }
+
+    pub fn into_transaction(self) -> TypedTransaction {
+        self.transaction
+    }
+}
+
+impl Default for UnverifiedTransaction {
+    fn default() -> Self {
+        TypedTransaction::default().into()
+    }
+}
+
+impl Deref for UnverifiedTransaction {
+    type Target = TypedTransaction;
+
+    fn deref(&self) -> &TypedTransaction {
+        &self.transaction
+    }
+}
+
+impl From<TypedTransaction> for UnverifiedTransaction {
+    fn from(tx: TypedTransaction) -> Self {
+        UnverifiedTransaction {
+            hash: tx.hash(),
+            transaction: tx,
+        }
+    }
+}
+
+impl From<Transaction> for UnverifiedTransaction {
+    fn from(tx: Transaction) -> Self {
+        TypedTransaction::Legacy(tx).into()
+    }
+}
+
+impl Encodable for UnverifiedTransaction {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.transaction.rlp_append(s);
+    }
+}
+
+impl Decodable for UnverifiedTransaction {
+    fn decode(d: &UntrustedRlp) -> Result<Self, DecoderError> {
+        // hash the bytes we were given instead of encoding the fields again
+        let hash = if d.is_list() {
+            keccak(d.as_raw())
+        } else {
+            keccak(d.data()?)
+        };
+        Ok(UnverifiedTransaction {
+            transaction: d.as_val()?,
+            hash,
+        })
+    }
+}
+
+/// A transaction with a checked signature and its recovered sender.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTransaction {
+    transaction: UnverifiedTransaction,
+    sender: Address,
+    public: Public,
+}
+
+impl SignedTransaction {
+    pub fn new(transaction: UnverifiedTransaction) -> Result<Self, TransactionError> {
+        let public = transaction.recover_public()?;
+        Ok(SignedTransaction {
+            transaction,
+            sender: crypto::public_to_address(&public),
+            public,
+        })
+    }
+
+    pub fn sender(&self) -> Address {
+        self.sender
+    }
+
+    pub fn public(&self) -> &Public {
+        &self.public
+    }
+
+    /// The address of the contract this transaction creates, if any.
+    pub fn created_address(&self) -> Option<Address> {
+        if self.recipient().is_create() {
+            Some(contract_address(&self.sender, self.nonce()))
+        } else {
+            None
+        }
+    }
+
+    pub fn deconstruct(self) -> (UnverifiedTransaction, Address, Public) {
+        (self.transaction, self.sender, self.public)
+    }
+}
+
+impl Deref for SignedTransaction {
+    type Target = UnverifiedTransaction;
+
+    fn deref(&self) -> &UnverifiedTransaction {
+        &self.transaction
+    }
+}
+
+impl From<SignedTransaction> for UnverifiedTransaction {
+    fn from(tx: SignedTransaction) -> Self {
+        tx.transaction
+    }
+}
+
+impl Encodable for SignedTransaction {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.transaction.rlp_append(s);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rustc_hex::FromHex;
     use rlp;
-    use super::{Recipient, Transaction};
-    use ethereum_types::{Address, Bloom, U256};
+    use super::{Recipient, SignedTransaction, Transaction, UnverifiedTransaction};
+    use ethereum_types::{Address, U256};
+    use crypto::{public_to_address, secret_to_public, Secret};
+    use error::TransactionError;
+    use keccak_hash::keccak;
+    use contract_address::contract_address;
+    use crypto::CURVE_ORDER;
+    use schedule::Schedule;
+    use typed_transaction::TypedTransaction;
 
     #[test]
     fn test_tx_decode() {
@@ -133,4 +427,165 @@ mod tests {
         );
         assert_eq!(tx.v, U256::from(28));
     }
+
+    #[test]
+    fn test_recipient_rlp() {
+        let create = Recipient::create();
+        assert!(create.is_create());
+        assert_eq!(rlp::encode(&create).into_vec(), vec![0x80]);
+        assert_eq!(rlp::decode::<Recipient>(&[0x80]), create);
+
+        let address = Address::from(0x1234);
+        let call = Recipient::from(address);
+        assert_eq!(call.address(), Some(&address));
+        assert_eq!(rlp::decode::<Recipient>(&rlp::encode(&call)), call);
+    }
+
+    fn legacy_v(tx: &SignedTransaction) -> U256 {
+        match ***tx {
+            TypedTransaction::Legacy(ref tx) => tx.v,
+            _ => panic!("not a legacy transaction"),
+        }
+    }
+
+    #[test]
+    fn test_sign_and_recover_sender() {
+        let secret = Secret::from(
+            "4646464646464646464646464646464646464646464646464646464646464646",
+        );
+        let sender = public_to_address(&secret_to_public(&secret).unwrap());
+        let mut tx = Transaction::new();
+        tx.nonce = 9;
+        tx.gas_limit = U256::from(21000);
+        tx.recipient = Recipient::call(Address::from(0x35));
+        tx.amount = U256::from(1000);
+
+        let legacy = tx.clone().sign(&secret, None).unwrap();
+        assert_eq!(legacy.sender(), sender);
+        assert_eq!(legacy.chain_id(), None);
+        let v = legacy_v(&legacy);
+        assert!(v == U256::from(27) || v == U256::from(28));
+
+        let protected = tx.sign(&secret, Some(1)).unwrap();
+        assert_eq!(protected.sender(), sender);
+        assert_eq!(protected.chain_id(), Some(1));
+        let v = legacy_v(&protected);
+        assert!(v == U256::from(37) || v == U256::from(38));
+
+        let decoded: UnverifiedTransaction = rlp::decode(&rlp::encode(&protected));
+        assert_eq!(SignedTransaction::new(decoded).unwrap(), protected);
+    }
+
+    #[test]
+    fn test_eip155_signing() {
+        let secret = Secret::from(
+            "4646464646464646464646464646464646464646464646464646464646464646",
+        );
+        let tx = Transaction {
+            nonce: 9,
+            gas_price: U256::from(20_000_000_000u64),
+            gas_limit: U256::from(21000),
+            recipient: Recipient::call(Address::from(
+                "3535353535353535353535353535353535353535"
+                    .from_hex()
+                    .unwrap()
+                    .as_slice(),
+            )),
+            amount: U256::from(1_000_000_000_000_000_000u64),
+            ..Transaction::new()
+        };
+        assert_eq!(
+            tx.signature_hash(Some(1)),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53".into()
+        );
+
+        let signed = tx.sign(&secret, Some(1)).unwrap();
+        let expected = "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83".from_hex().unwrap();
+        assert_eq!(rlp::encode(&signed).into_vec(), expected);
+
+        let decoded: UnverifiedTransaction = rlp::decode(&expected);
+        assert_eq!(decoded.chain_id(), Some(1));
+        assert_eq!(decoded.standard_v(), Some(0));
+        assert_eq!(
+            decoded.clone().verify(Some(1)).unwrap().sender(),
+            Address::from(
+                "9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"
+                    .from_hex()
+                    .unwrap()
+                    .as_slice()
+            )
+        );
+        assert_eq!(
+            decoded.verify(Some(2)),
+            Err(TransactionError::InvalidChainId {
+                expected: Some(2),
+                got: Some(1),
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_v() {
+        let tx_rlp = "f86103018207d094b94f5374fce5edbc8e2a8697c15331677e6ebf0b0a8255441ca098ff921201554726367d2be8c804a7ff89ccf285ebc57dff8ae4c44b9c19ac4aa08887321be575c8095f789dd4c743dfe42c1820f9231f98a962b210e3ac2452a3".from_hex().unwrap();
+        let mut tx: Transaction = rlp::decode(&tx_rlp);
+        assert_eq!(tx.chain_id(), None);
+        assert_eq!(tx.standard_v(), Some(1));
+
+        tx.v = U256::from(30);
+        assert_eq!(tx.standard_v(), None);
+        assert!(UnverifiedTransaction::from(tx).verify(None).is_err());
+    }
+
+    #[test]
+    fn test_hash_is_cached_on_decode() {
+        let tx_rlp = "f86103018207d094b94f5374fce5edbc8e2a8697c15331677e6ebf0b0a8255441ca098ff921201554726367d2be8c804a7ff89ccf285ebc57dff8ae4c44b9c19ac4aa08887321be575c8095f789dd4c743dfe42c1820f9231f98a962b210e3ac2452a3".from_hex().unwrap();
+        let tx: Transaction = rlp::decode(&tx_rlp);
+        let unverified: UnverifiedTransaction = rlp::decode(&tx_rlp);
+        assert_eq!(tx.hash(), keccak(&tx_rlp));
+        assert_eq!(unverified.hash(), tx.hash());
+        assert_eq!(UnverifiedTransaction::from(tx).hash(), unverified.hash());
+    }
+
+    #[test]
+    fn test_verify_basic() {
+        let secret = Secret::from(
+            "4646464646464646464646464646464646464646464646464646464646464646",
+        );
+        let schedule = Schedule::default();
+        let mut tx = Transaction::new();
+        tx.payload = vec![0, 0, 1];
+        // creation: 53000 + 2 * 4 + 16
+        assert_eq!(
+            TypedTransaction::from(tx.clone()).intrinsic_gas(&schedule),
+            U256::from(53024)
+        );
+
+        tx.gas_limit = U256::from(53023);
+        let signed = tx.clone().sign(&secret, None).unwrap();
+        assert_eq!(
+            signed.verify_basic(&schedule),
+            Err(TransactionError::NotEnoughBaseGas {
+                required: U256::from(53024),
+                got: U256::from(53023),
+            })
+        );
+
+        tx.gas_limit = U256::from(53024);
+        let signed = tx.sign(&secret, None).unwrap();
+        assert_eq!(signed.verify_basic(&schedule), Ok(()));
+        assert_eq!(
+            signed.created_address(),
+            Some(contract_address(&signed.sender(), 0))
+        );
+
+        // flipping s to n - s keeps the signature valid but malleable
+        let mut high_s = UnverifiedTransaction::from(signed).into_transaction();
+        if let TypedTransaction::Legacy(ref mut tx) = high_s {
+            tx.s = *CURVE_ORDER - tx.s;
+        }
+        assert_eq!(
+            UnverifiedTransaction::from(high_s).verify_basic(&schedule),
+            Err(TransactionError::HighS)
+        );
+    }
 }
@@ -0,0 +1,588 @@
+use std::cmp;
+use ethereum_types::{Address, H256, U256};
+use bytes::Bytes;
+use keccak_hash::keccak;
+use rlp::{self, Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
+use crypto::{self, Secret, Signature};
+use error::TransactionError;
+use schedule::Schedule;
+use transaction::{Recipient, SignedTransaction, Transaction};
+
+pub const LEGACY_TX_TYPE: u8 = 0x00;
+pub const ACCESS_LIST_TX_TYPE: u8 = 0x01;
+pub const DYNAMIC_FEE_TX_TYPE: u8 = 0x02;
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct AccessListItem {
+    pub address: Address,
+    pub storage_keys: Vec<H256>,
+}
+
+pub type AccessList = Vec<AccessListItem>;
+
+impl Encodable for AccessListItem {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2);
+        s.append(&self.address);
+        s.append_list(&self.storage_keys);
+    }
+}
+
+impl Decodable for AccessListItem {
+    fn decode(d: &UntrustedRlp) -> Result<Self, DecoderError> {
+        if d.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(AccessListItem {
+            address: d.val_at(0)?,
+            storage_keys: d.list_at(1)?,
+        })
+    }
+}
+
+/// EIP-2930 transaction, signed over `0x01 || rlp(fields)`.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct AccessListTransaction {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub gas_price: U256,
+    pub gas_limit: U256,
+    pub recipient: Recipient,
+    pub amount: U256,
+    pub payload: Bytes,
+    pub access_list: AccessList,
+    pub y_parity: u8,
+    pub r: U256,
+    pub s: U256,
+}
+
+impl AccessListTransaction {
+    pub fn hash(&self) -> H256 {
+        typed_hash(ACCESS_LIST_TX_TYPE, &rlp::encode(self))
+    }
+
+    pub fn signature_hash(&self) -> H256 {
+        let mut s = RlpStream::new();
+        self.stream_fields(&mut s, false);
+        typed_hash(ACCESS_LIST_TX_TYPE, s.as_raw())
+    }
+
+    pub fn sign(mut self, secret: &Secret) -> Result<SignedTransaction, TransactionError> {
+        let signature = crypto::sign(secret, &self.signature_hash())?;
+        self.y_parity = signature.v;
+        self.r = U256::from(signature.r);
+        self.s = U256::from(signature.s);
+        SignedTransaction::new(TypedTransaction::AccessList(self).into())
+    }
+
+    fn stream_fields(&self, s: &mut RlpStream, with_signature: bool) {
+        s.begin_list(if with_signature { 11 } else { 8 });
+        s.append(&self.chain_id);
+        s.append(&self.nonce);
+        s.append(&self.gas_price);
+        s.append(&self.gas_limit);
+        s.append(&self.recipient);
+        s.append(&self.amount);
+        s.append(&self.payload);
+        s.append_list(&self.access_list);
+        if with_signature {
+            s.append(&self.y_parity);
+            s.append(&self.r);
+            s.append(&self.s);
+        }
+    }
+}
+
+impl Encodable for AccessListTransaction {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.stream_fields(s, true);
+    }
+}
+
+impl Decodable for AccessListTransaction {
+    fn decode(d: &UntrustedRlp) -> Result<Self, DecoderError> {
+        if d.item_count()? != 11 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(AccessListTransaction {
+            chain_id: d.val_at(0)?,
+            nonce: d.val_at(1)?,
+            gas_price: d.val_at(2)?,
+            gas_limit: d.val_at(3)?,
+            recipient: d.val_at(4)?,
+            amount: d.val_at(5)?,
+            payload: d.val_at(6)?,
+            access_list: d.list_at(7)?,
+            y_parity: d.val_at(8)?,
+            r: d.val_at(9)?,
+            s: d.val_at(10)?,
+        })
+    }
+}
+
+/// EIP-1559 transaction, signed over `0x02 || rlp(fields)`.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct DynamicFeeTransaction {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub max_priority_fee_per_gas: U256,
+    pub max_fee_per_gas: U256,
+    pub gas_limit: U256,
+    pub recipient: Recipient,
+    pub amount: U256,
+    pub payload: Bytes,
+    pub access_list: AccessList,
+    pub y_parity: u8,
+    pub r: U256,
+    pub s: U256,
+}
+
+impl DynamicFeeTransaction {
+    pub fn hash(&self) -> H256 {
+        typed_hash(DYNAMIC_FEE_TX_TYPE, &rlp::encode(self))
+    }
+
+    pub fn signature_hash(&self) -> H256 {
+        let mut s = RlpStream::new();
+        self.stream_fields(&mut s, false);
+        typed_hash(DYNAMIC_FEE_TX_TYPE, s.as_raw())
+    }
+
+    pub fn sign(mut self, secret: &Secret) -> Result<SignedTransaction, TransactionError> {
+        let signature = crypto::sign(secret, &self.signature_hash())?;
+        self.y_parity = signature.v;
+        self.r = U256::from(signature.r);
+        self.s = U256::from(signature.s);
+        SignedTransaction::new(TypedTransaction::DynamicFee(self).into())
+    }
+
+    fn stream_fields(&self, s: &mut RlpStream, with_signature: bool) {
+        s.begin_list(if with_signature { 12 } else { 9 });
+        s.append(&self.chain_id);
+        s.append(&self.nonce);
+        s.append(&self.max_priority_fee_per_gas);
+        s.append(&self.max_fee_per_gas);
+        s.append(&self.gas_limit);
+        s.append(&self.recipient);
+        s.append(&self.amount);
+        s.append(&self.payload);
+        s.append_list(&self.access_list);
+        if with_signature {
+            s.append(&self.y_parity);
+            s.append(&self.r);
+            s.append(&self.s);
+        }
+    }
+}
+
+impl Encodable for DynamicFeeTransaction {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.stream_fields(s, true);
+    }
+}
+
+impl Decodable for DynamicFeeTransaction {
+    fn decode(d: &UntrustedRlp) -> Result<Self, DecoderError> {
+        if d.item_count()? != 12 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(DynamicFeeTransaction {
+            chain_id: d.val_at(0)?,
+            nonce: d.val_at(1)?,
+            max_priority_fee_per_gas: d.val_at(2)?,
+            max_fee_per_gas: d.val_at(3)?,
+            gas_limit: d.val_at(4)?,
+            recipient: d.val_at(5)?,
+            amount: d.val_at(6)?,
+            payload: d.val_at(7)?,
+            access_list: d.list_at(8)?,
+            y_parity: d.val_at(9)?,
+            r: d.val_at(10)?,
+            s: d.val_at(11)?,
+        })
+    }
+}
+
+/// Any transaction kind that can appear in a block (EIP-2718).
+///
+/// Inside a block body a legacy transaction is an RLP list while a typed
+/// transaction is an RLP string holding `type || rlp(fields)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypedTransaction {
+    Legacy(Transaction),
+    AccessList(AccessListTransaction),
+    DynamicFee(DynamicFeeTransaction),
+}
+
+impl Default for TypedTransaction {
+    fn default() -> Self {
+        TypedTransaction::Legacy(Transaction::default())
+    }
+}
+
+impl TypedTransaction {
+    pub fn tx_type(&self) -> u8 {
+        match *self {
+            TypedTransaction::Legacy(_) => LEGACY_TX_TYPE,
+            TypedTransaction::AccessList(_) => ACCESS_LIST_TX_TYPE,
+            TypedTransaction::DynamicFee(_) => DYNAMIC_FEE_TX_TYPE,
+        }
+    }
+
+    pub fn nonce(&self) -> u64 {
+        match *self {
+            TypedTransaction::Legacy(ref tx) => tx.nonce,
+            TypedTransaction::AccessList(ref tx) => tx.nonce,
+            TypedTransaction::DynamicFee(ref tx) => tx.nonce,
+        }
+    }
+
+    /// The highest price per gas the sender may pay, `max_fee_per_gas` for
+    /// dynamic fee transactions.
+    pub fn gas_price(&self) -> &U256 {
+        match *self {
+            TypedTransaction::Legacy(ref tx) => &tx.gas_price,
+            TypedTransaction::AccessList(ref tx) => &tx.gas_price,
+            TypedTransaction::DynamicFee(ref tx) => &tx.max_fee_per_gas,
+        }
+    }
+
+    pub fn effective_gas_price(&self, base_fee: Option<U256>) -> U256 {
+        match (self, base_fee) {
+            (TypedTransaction::DynamicFee(tx), Some(base_fee)) => cmp::min(
+                tx.max_fee_per_gas,
+                base_fee.saturating_add(tx.max_priority_fee_per_gas),
+            ),
+            _ => *self.gas_price(),
+        }
+    }
+
+    pub fn gas_limit(&self) -> &U256 {
+        match *self {
+            TypedTransaction::Legacy(ref tx) => &tx.gas_limit,
+            TypedTransaction::AccessList(ref tx) => &tx.gas_limit,
+            TypedTransaction::DynamicFee(ref tx) => &tx.gas_limit,
+        }
+    }
+
+    pub fn recipient(&self) -> &Recipient {
+        match *self {
+            TypedTransaction::Legacy(ref tx) => &tx.recipient,
+            TypedTransaction::AccessList(ref tx) => &tx.recipient,
+            TypedTransaction::DynamicFee(ref tx) => &tx.recipient,
+        }
+    }
+
+    pub fn amount(&self) -> &U256 {
+        match *self {
+            TypedTransaction::Legacy(ref tx) => &tx.amount,
+            TypedTransaction::AccessList(ref tx) => &tx.amount,
+            TypedTransaction::DynamicFee(ref tx) => &tx.amount,
+        }
+    }
+
+    pub fn payload(&self) -> &Bytes {
+        match *self {
+            TypedTransaction::Legacy(ref tx) => &tx.payload,
+            TypedTransaction::AccessList(ref tx) => &tx.payload,
+            TypedTransaction::DynamicFee(ref tx) => &tx.payload,
+        }
+    }
+
+    pub fn access_list(&self) -> &[AccessListItem] {
+        match *self {
+            TypedTransaction::Legacy(_) => &[],
+            TypedTransaction::AccessList(ref tx) => &tx.access_list,
+            TypedTransaction::DynamicFee(ref tx) => &tx.access_list,
+        }
+    }
+
+    /// Gas charged before execution: the base cost, the creation surcharge,
+    /// the payload bytes and the access list entries.
+    pub fn intrinsic_gas(&self, schedule: &Schedule) -> U256 {
+        let mut gas = if self.recipient().is_create() {
+            schedule.tx_create_gas
+        } else {
+            schedule.tx_gas
+        };
+        let zeros = self.payload().iter().filter(|b| **b == 0).count() as u64;
+        let non_zeros = self.payload().len() as u64 - zeros;
+        gas += zeros * schedule.tx_data_zero_gas + non_zeros * schedule.tx_data_non_zero_gas;
+
+        let mut gas = U256::from(gas);
+        for item in self.access_list() {
+            gas = gas + U256::from(schedule.access_list_address_gas)
+                + U256::from(schedule.access_list_storage_key_gas)
+                    * U256::from(item.storage_keys.len());
+        }
+        gas
+    }
+
+    pub fn chain_id(&self) -> Option<u64> {
+        match *self {
+            TypedTransaction::Legacy(ref tx) => tx.chain_id(),
+            TypedTransaction::AccessList(ref tx) => Some(tx.chain_id),
+            TypedTransaction::DynamicFee(ref tx) => Some(tx.chain_id),
+        }
+    }
+
+    pub fn standard_v(&self) -> Option<u8> {
+        let y_parity = match *self {
+            TypedTransaction::Legacy(ref tx) => return tx.standard_v(),
+            TypedTransaction::AccessList(ref tx) => tx.y_parity,
+            TypedTransaction::DynamicFee(ref tx) => tx.y_parity,
+        };
+        if y_parity <= 1 {
+            Some(y_parity)
+        } else {
+            None
+        }
+    }
+
+    pub fn r(&self) -> &U256 {
+        match *self {
+            TypedTransaction::Legacy(ref tx) => &tx.r,
+            TypedTransaction::AccessList(ref tx) => &tx.r,
+            TypedTransaction::DynamicFee(ref tx) => &tx.r,
+        }
+    }
+
+    pub fn s(&self) -> &U256 {
+        match *self {
+            TypedTransaction::Legacy(ref tx) => &tx.s,
+            TypedTransaction::AccessList(ref tx) => &tx.s,
+            TypedTransaction::DynamicFee(ref tx) => &tx.s,
+        }
+    }
+
+    /// The transaction identity, `keccak(envelope())`.
+    pub fn hash(&self) -> H256 {
+        match *self {
+            TypedTransaction::Legacy(ref tx) => tx.hash(),
+            TypedTransaction::AccessList(ref tx) => tx.hash(),
+            TypedTransaction::DynamicFee(ref tx) => tx.hash(),
+        }
+    }
+
+    pub fn signature_hash(&self) -> H256 {
+        match *self {
+            TypedTransaction::Legacy(ref tx) => tx.signature_hash(tx.chain_id()),
+            TypedTransaction::AccessList(ref tx) => tx.signature_hash(),
+            TypedTransaction::DynamicFee(ref tx) => tx.signature_hash(),
+        }
+    }
+
+    pub(crate) fn signature(&self) -> Result<Signature, TransactionError> {
+        let v = self.standard_v()
+            .ok_or(TransactionError::InvalidSignature(crypto::Error::InvalidSignature))?;
+        Ok(Signature {
+            r: H256::from(self.r()),
+            s: H256::from(self.s()),
+            v,
+        })
+    }
+
+    /// The opaque EIP-2718 encoding: the RLP list for legacy transactions,
+    /// `type || rlp(fields)` otherwise.
+    pub fn envelope(&self) -> Bytes {
+        let mut out = Vec::new();
+        match *self {
+            TypedTransaction::Legacy(ref tx) => out.extend_from_slice(&rlp::encode(tx)),
+            TypedTransaction::AccessList(ref tx) => {
+                out.push(ACCESS_LIST_TX_TYPE);
+                out.extend_from_slice(&rlp::encode(tx));
+            }
+            TypedTransaction::DynamicFee(ref tx) => {
+                out.push(DYNAMIC_FEE_TX_TYPE);
+                out.extend_from_slice(&rlp::encode(tx));
+            }
+        }
+        out
+    }
+
+    pub fn from_envelope(bytes: &[u8]) -> Result<Self, DecoderError> {
+        match bytes.first() {
+            None => Err(DecoderError::RlpIsTooShort),
+            Some(&ACCESS_LIST_TX_TYPE) => {
+                UntrustedRlp::new(&bytes[1..]).as_val().map(TypedTransaction::AccessList)
+            }
+            Some(&DYNAMIC_FEE_TX_TYPE) => {
+                UntrustedRlp::new(&bytes[1..]).as_val().map(TypedTransaction::DynamicFee)
+            }
+            Some(&b) if b >= 0xc0 => UntrustedRlp::new(bytes).as_val().map(TypedTransaction::Legacy),
+            Some(_) => Err(DecoderError::Custom("unknown transaction type")),
+        }
+    }
+}
+
+impl From<Transaction> for TypedTransaction {
+    fn from(tx: Transaction) -> Self {
+        TypedTransaction::Legacy(tx)
+    }
+}
+
+impl From<AccessListTransaction> for TypedTransaction {
+    fn from(tx: AccessListTransaction) -> Self {
+        TypedTransaction::AccessList(tx)
+    }
+}
+
+impl From<DynamicFeeTransaction> for TypedTransaction {
+    fn from(tx: DynamicFeeTransaction) -> Self {
+        TypedTransaction::DynamicFee(tx)
+    }
+}
+
+impl Encodable for TypedTransaction {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        match *self {
+            TypedTransaction::Legacy(ref tx) => tx.rlp_append(s),
+            _ => {
+                s.append_internal(&self.envelope());
+            }
+        }
+    }
+}
+
+impl Decodable for TypedTransaction {
+    fn decode(d: &UntrustedRlp) -> Result<Self, DecoderError> {
+        if d.is_list() {
+            d.as_val().map(TypedTransaction::Legacy)
+        } else {
+            let bytes = d.data()?;
+            if bytes.first().is_some_and(|b| *b >= 0xc0) {
+                // a legacy transaction must not be wrapped in a string
+                return Err(DecoderError::Custom("unknown transaction type"));
+            }
+            TypedTransaction::from_envelope(bytes)
+        }
+    }
+}
+
+fn typed_hash(tx_type: u8, fields: &[u8]) -> H256 {
+    let mut data = Vec::with_capacity(fields.len() + 1);
+    data.push(tx_type);
+    data.extend_from_slice(fields);
+    keccak(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethereum_types::{Address, H256, U256};
+    use keccak_hash::keccak;
+    use rlp;
+    use rustc_hex::FromHex;
+    use crypto::{public_to_address, secret_to_public, Secret};
+    use transaction::{Recipient, SignedTransaction, UnverifiedTransaction};
+    use super::{AccessListItem, AccessListTransaction, DynamicFeeTransaction, TypedTransaction};
+
+    fn secret() -> Secret {
+        Secret::from("4646464646464646464646464646464646464646464646464646464646464646")
+    }
+
+    fn access_list() -> Vec<AccessListItem> {
+        vec![AccessListItem {
+            address: Address::from(0xde),
+            storage_keys: vec![H256::from(1), H256::from(2)],
+        }]
+    }
+
+    #[test]
+    fn test_access_list_tx_round_trip() {
+        let tx = AccessListTransaction {
+            chain_id: 1,
+            nonce: 7,
+            gas_price: U256::from(1_000_000_000),
+            gas_limit: U256::from(30_000),
+            recipient: Recipient::call(Address::from(0x35)),
+            amount: U256::from(1),
+            payload: vec![0xab, 0xcd],
+            access_list: access_list(),
+            ..Default::default()
+        };
+
+        let signed = tx.sign(&secret()).unwrap();
+        assert_eq!(
+            signed.sender(),
+            public_to_address(&secret_to_public(&secret()).unwrap())
+        );
+        let envelope = signed.envelope();
+        assert_eq!(envelope[0], 0x01);
+        let decoded = TypedTransaction::from_envelope(&envelope).unwrap();
+        assert_eq!(&decoded, &**signed);
+    }
+
+    #[test]
+    fn test_dynamic_fee_tx_round_trip() {
+        let tx = DynamicFeeTransaction {
+            chain_id: 5,
+            nonce: 1,
+            max_priority_fee_per_gas: U256::from(2),
+            max_fee_per_gas: U256::from(100),
+            gas_limit: U256::from(21_000),
+            recipient: Recipient::create(),
+            access_list: access_list(),
+            ..Default::default()
+        };
+        let signed = tx.sign(&secret()).unwrap();
+        assert_eq!(signed.chain_id(), Some(5));
+        assert_eq!(signed.effective_gas_price(Some(U256::from(10))), U256::from(12));
+        assert_eq!(signed.effective_gas_price(Some(U256::from(99))), U256::from(100));
+
+        // inside a list the typed transaction is an rlp string
+        let encoded = rlp::encode(&signed).into_vec();
+        assert!(encoded[0] >= 0x80 && encoded[0] < 0xc0);
+        let decoded: UnverifiedTransaction = rlp::decode(&encoded);
+        assert_eq!(decoded.hash(), keccak(signed.envelope()));
+        assert_eq!(decoded.hash(), TypedTransaction::hash(&signed));
+        assert_eq!(SignedTransaction::new(decoded).unwrap(), signed);
+    }
+
+    #[test]
+    fn test_access_list_signature_hash() {
+        // go-ethereum's EIP-2930 signer vector for an empty transaction
+        let tx = AccessListTransaction {
+            chain_id: 1,
+            nonce: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            tx.signature_hash(),
+            "846ad7672f2a3a40c1f959cd4a8ad21786d620077084d84c8d7c077714caa139".into()
+        );
+    }
+
+    #[test]
+    fn test_mainnet_dynamic_fee_txs() {
+        let cases = [
+            (
+                "02f872018307910d808507204d2cb1827d0094388c818ca8b9251b393131c08a736a67ccb19297880320d04823e2701c80c001a0cf024f4815304df2867a1a74e9d2707b6abda0337d2d54a4438d453f4160f190a07ac0e6b3bc9395b5b9c8b9e6d77204a236577a5b18467b9175c01de4faa208d9",
+                "86718885c4b4218c6af87d3d0b0d83e3cc465df2a05c048aa4db9f1a6f9de91f",
+                "95222290dd7278aa3ddd389cc1e1d165cc4bafe5",
+            ),
+            (
+                "02f86f0102843b9aca0085029e7822d68298f094d9e1459a7a482635700cbc20bbaf52d495ab9c9680841b55ba3ac080a0c199674fcb29f353693dd779c017823b954b3c69dffa3cd6b2a6ff7888798039a028ca912de909e7e6cdef9cdcaf24c54dd8c1032946dfa1d85c206b32a9064fe8",
+                "ce4dc6d7a7549a98ee3b071b67e970879ff51b5b95d1c340bacd80fa1e1aab31",
+                "001e2b7de757ba469a57bf6b23d982458a07efce",
+            ),
+        ];
+        for &(raw, hash, sender) in &cases {
+            let raw = raw.from_hex().unwrap();
+            let tx = TypedTransaction::from_envelope(&raw).unwrap();
+            assert_eq!(tx.tx_type(), 0x02);
+            assert_eq!(tx.chain_id(), Some(1));
+            assert_eq!(tx.envelope(), raw);
+            assert_eq!(tx.hash(), hash.into());
+
+            let signed = SignedTransaction::new(tx.into()).unwrap();
+            assert_eq!(signed.hash(), hash.into());
+            assert_eq!(signed.sender(), sender.into());
+        }
+    }
+
+    #[test]
+    fn test_reject_unknown_type() {
+        assert!(TypedTransaction::from_envelope(&[0x03, 0xc0]).is_err());
+        assert!(TypedTransaction::from_envelope(&[]).is_err());
+    }
+}
@@ -0,0 +1,407 @@
+//! Staged header verification: checks on the header alone, checks against
+//! its parent, and seal checks supplied by the consensus engine.
+
+use std::cmp;
+use std::collections::HashSet;
+use ethereum_types::{H256, U256};
+use block::Block;
+use error::BlockError;
+use header::Header;
+
+/// Chain parameters the verification rules depend on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationParams {
+    pub maximum_extra_data_size: usize,
+    pub min_gas_limit: U256,
+    pub gas_limit_bound_divisor: U256,
+    pub maximum_uncle_count: usize,
+    /// How many generations back an uncle may be from the including block.
+    pub maximum_uncle_age: u64,
+}
+
+impl Default for VerificationParams {
+    fn default() -> Self {
+        VerificationParams {
+            maximum_extra_data_size: 32,
+            min_gas_limit: U256::from(5000),
+            gas_limit_bound_divisor: U256::from(1024),
+            maximum_uncle_count: 2,
+            maximum_uncle_age: 6,
+        }
+    }
+}
+
+/// Seal checks, implemented by whatever produces the seal.
+pub trait SealVerifier {
+    /// Checks that need only the header itself.
+    fn verify_seal(&self, _header: &Header) -> Result<(), BlockError> {
+        Ok(())
+    }
+
+    /// Checks that need the parent, e.g. expected difficulty.
+    fn verify_seal_family(&self, _header: &Header, _parent: &Header) -> Result<(), BlockError> {
+        Ok(())
+    }
+}
+
+/// Accepts any seal.
+pub struct NoSeal;
+
+impl SealVerifier for NoSeal {}
+
+pub fn verify_header_params(header: &Header, params: &VerificationParams) -> Result<(), BlockError> {
+    if header.extra_data.len() > params.maximum_extra_data_size {
+        return Err(BlockError::ExtraDataTooLong {
+            max: params.maximum_extra_data_size,
+            got: header.extra_data.len(),
+        });
+    }
+    if header.gas_used > header.gas_limit {
+        return Err(BlockError::TooMuchGasUsed {
+            gas_limit: header.gas_limit,
+            gas_used: header.gas_used,
+        });
+    }
+    if header.timestamp == 0 {
+        return Err(BlockError::ZeroTimestamp);
+    }
+    Ok(())
+}
+
+pub fn verify_header_family(
+    header: &Header,
+    parent: &Header,
+    params: &VerificationParams,
+) -> Result<(), BlockError> {
+    let parent_hash = parent.hash();
+    if header.parent_hash != parent_hash {
+        return Err(BlockError::InvalidParentHash {
+            expected: parent_hash,
+            got: header.parent_hash,
+        });
+    }
+    let number = parent.number.checked_add(1).ok_or(BlockError::NumberOverflow)?;
+    if header.number != number {
+        return Err(BlockError::InvalidNumber {
+            expected: number,
+            got: header.number,
+        });
+    }
+    if header.timestamp <= parent.timestamp {
+        return Err(BlockError::InvalidTimestamp {
+            parent: parent.timestamp,
+            got: header.timestamp,
+        });
+    }
+
+    // the gas limit must move by less than the bound, so both ends are one
+    // step inside it
+    let bound = parent.gas_limit / params.gas_limit_bound_divisor;
+    let min = cmp::max(
+        parent.gas_limit.saturating_sub(bound) + U256::one(),
+        params.min_gas_limit,
+    );
+    let max = parent.gas_limit.saturating_add(bound).saturating_sub(U256::one());
+    if header.gas_limit < min || header.gas_limit > max {
+        return Err(BlockError::InvalidGasLimit {
+            min,
+            max,
+            got: header.gas_limit,
+        });
+    }
+    Ok(())
+}
+
+/// Runs every stage in order, stopping at the first failed rule. Without a
+/// parent (genesis) only the standalone checks run.
+pub fn verify_header<S: SealVerifier + ?Sized>(
+    header: &Header,
+    parent: Option<&Header>,
+    params: &VerificationParams,
+    seal: &S,
+) -> Result<(), BlockError> {
+    verify_header_params(header, params)?;
+    seal.verify_seal(header)?;
+    if let Some(parent) = parent {
+        verify_header_family(header, parent, params)?;
+        seal.verify_seal_family(header, parent)?;
+    }
+    Ok(())
+}
+
+/// Checks a block's uncles. `ancestry` holds the block's recent ancestors,
+/// parent first, going back at least `maximum_uncle_age + 1` generations
+/// where the chain is that long; `included_uncles` holds the hashes of
+/// uncles those ancestors already included.
+pub fn verify_block_uncles<S: SealVerifier + ?Sized>(
+    block: &Block,
+    ancestry: &[Header],
+    included_uncles: &[H256],
+    params: &VerificationParams,
+    seal: &S,
+) -> Result<(), BlockError> {
+    if block.uncles.len() > params.maximum_uncle_count {
+        return Err(BlockError::TooManyUncles {
+            max: params.maximum_uncle_count,
+            got: block.uncles.len(),
+        });
+    }
+    let uncles_hash = block.compute_uncles_hash();
+    if block.header.uncles_hash != uncles_hash {
+        return Err(BlockError::InvalidUnclesHash {
+            expected: uncles_hash,
+            got: block.header.uncles_hash,
+        });
+    }
+
+    let number = block.header.number;
+    let min = number.saturating_sub(params.maximum_uncle_age);
+    let max = number.saturating_sub(1);
+    let mut ancestors: HashSet<H256> = ancestry.iter().map(Header::hash).collect();
+    ancestors.insert(block.header.hash());
+    let mut seen = HashSet::new();
+    for uncle in &block.uncles {
+        let hash = uncle.hash();
+        if !seen.insert(hash) {
+            return Err(BlockError::DuplicateUncle(hash));
+        }
+        if ancestors.contains(&hash) {
+            return Err(BlockError::UncleIsAncestor(hash));
+        }
+        if included_uncles.contains(&hash) {
+            return Err(BlockError::UncleAlreadyIncluded(hash));
+        }
+        if number == 0 || uncle.number < min || uncle.number > max {
+            return Err(BlockError::UncleOutOfBounds {
+                min,
+                max,
+                got: uncle.number,
+            });
+        }
+        let parent = ancestry
+            .iter()
+            .find(|ancestor| ancestor.hash() == uncle.parent_hash)
+            .ok_or(BlockError::UncleParentNotInChain(uncle.parent_hash))?;
+        verify_header(uncle, Some(parent), params, seal)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ethereum_types::U256;
+    use block::Block;
+    use error::BlockError;
+    use header::Header;
+    use super::{verify_block_uncles, verify_header, NoSeal, SealVerifier, VerificationParams};
+
+    fn parent() -> Header {
+        Header {
+            number: 10,
+            timestamp: 1000,
+            gas_limit: U256::from(1_024_000),
+            ..Header::default()
+        }
+    }
+
+    fn child_of(parent: &Header) -> Header {
+        Header {
+            parent_hash: parent.hash(),
+            number: parent.number + 1,
+            timestamp: parent.timestamp + 15,
+            gas_limit: parent.gas_limit,
+            ..Header::default()
+        }
+    }
+
+    #[test]
+    fn test_standalone_rules() {
+        let params = VerificationParams::default();
+        let mut header = child_of(&parent());
+        assert_eq!(verify_header(&header, None, &params, &NoSeal), Ok(()));
+
+        header.extra_data = vec![0; 33];
+        assert_eq!(
+            verify_header(&header, None, &params, &NoSeal),
+            Err(BlockError::ExtraDataTooLong { max: 32, got: 33 })
+        );
+        header.extra_data = vec![];
+
+        header.gas_used = header.gas_limit + 1.into();
+        assert_eq!(
+            verify_header(&header, None, &params, &NoSeal),
+            Err(BlockError::TooMuchGasUsed {
+                gas_limit: header.gas_limit,
+                gas_used: header.gas_used,
+            })
+        );
+        header.gas_used = U256::zero();
+
+        header.timestamp = 0;
+        assert_eq!(
+            verify_header(&header, None, &params, &NoSeal),
+            Err(BlockError::ZeroTimestamp)
+        );
+    }
+
+    #[test]
+    fn test_family_rules() {
+        let params = VerificationParams::default();
+        let parent = parent();
+        let good = child_of(&parent);
+        assert_eq!(verify_header(&good, Some(&parent), &params, &NoSeal), Ok(()));
+
+        let mut header = good.clone();
+        header.number = 12;
+        header.parent_hash = parent.hash();
+        assert_eq!(
+            verify_header(&header, Some(&parent), &params, &NoSeal),
+            Err(BlockError::InvalidNumber { expected: 11, got: 12 })
+        );
+
+        let mut header = good.clone();
+        header.timestamp = parent.timestamp;
+        assert_eq!(
+            verify_header(&header, Some(&parent), &params, &NoSeal),
+            Err(BlockError::InvalidTimestamp { parent: 1000, got: 1000 })
+        );
+
+        let mut header = good.clone();
+        header.parent_hash = 1.into();
+        assert_eq!(
+            verify_header(&header, Some(&parent), &params, &NoSeal),
+            Err(BlockError::InvalidParentHash {
+                expected: parent.hash(),
+                got: 1.into(),
+            })
+        );
+
+        // the bound is parent / 1024 = 1000, reported bounds are inclusive
+        let mut header = good.clone();
+        header.gas_limit = U256::from(1_024_999);
+        assert_eq!(verify_header(&header, Some(&parent), &params, &NoSeal), Ok(()));
+        header.gas_limit = U256::from(1_023_001);
+        assert_eq!(verify_header(&header, Some(&parent), &params, &NoSeal), Ok(()));
+        header.gas_limit = U256::from(1_025_000);
+        assert_eq!(
+            verify_header(&header, Some(&parent), &params, &NoSeal),
+            Err(BlockError::InvalidGasLimit {
+                min: U256::from(1_023_001),
+                max: U256::from(1_024_999),
+                got: U256::from(1_025_000),
+            })
+        );
+
+        // the configured minimum is inclusive as well
+        let params = VerificationParams {
+            min_gas_limit: U256::from(1_024_000),
+            ..VerificationParams::default()
+        };
+        header.gas_limit = U256::from(1_024_000);
+        assert_eq!(verify_header(&header, Some(&parent), &params, &NoSeal), Ok(()));
+        header.gas_limit = U256::from(1_023_999);
+        assert_eq!(
+            verify_header(&header, Some(&parent), &params, &NoSeal),
+            Err(BlockError::InvalidGasLimit {
+                min: U256::from(1_024_000),
+                max: U256::from(1_024_999),
+                got: U256::from(1_023_999),
+            })
+        );
+
+        let mut parent = parent;
+        parent.number = u64::MAX;
+        let mut header = good.clone();
+        header.parent_hash = parent.hash();
+        assert_eq!(
+            verify_header(&header, Some(&parent), &params, &NoSeal),
+            Err(BlockError::NumberOverflow)
+        );
+    }
+
+    #[test]
+    fn test_seal_verifier_is_consulted() {
+        struct RejectFamily;
+        impl SealVerifier for RejectFamily {
+            fn verify_seal_family(&self, _: &Header, _: &Header) -> Result<(), BlockError> {
+                Err(BlockError::ZeroTimestamp)
+            }
+        }
+
+        let params = VerificationParams::default();
+        let parent = parent();
+        let header = child_of(&parent);
+        assert_eq!(verify_header(&header, None, &params, &RejectFamily), Ok(()));
+        assert!(verify_header(&header, Some(&parent), &params, &RejectFamily).is_err());
+    }
+
+    #[test]
+    fn test_uncle_rules() {
+        let params = VerificationParams::default();
+        // ancestry of block 20, parent first
+        let mut chain = vec![parent()];
+        for _ in 0..9 {
+            let next = child_of(chain.last().unwrap());
+            chain.push(next);
+        }
+        let ancestry: Vec<Header> = chain.iter().rev().cloned().collect();
+        let uncle_of = |ancestor: &Header| {
+            let mut uncle = child_of(ancestor);
+            uncle.timestamp += 1;
+            uncle
+        };
+        let block_with = |uncles: Vec<Header>| {
+            let mut block = Block {
+                header: child_of(&ancestry[0]),
+                transactions: vec![],
+                uncles,
+            };
+            block.header.uncles_hash = block.compute_uncles_hash();
+            block
+        };
+        let verify = |block: &Block, included: &[_]| verify_block_uncles(block, &ancestry, included, &params, &NoSeal);
+
+        // sibling of the parent, and of the oldest allowed generation
+        let uncle = uncle_of(&ancestry[1]);
+        let oldest = uncle_of(&ancestry[6]);
+        assert_eq!(verify(&block_with(vec![uncle.clone(), oldest]), &[]), Ok(()));
+
+        let mut block = block_with(vec![uncle.clone()]);
+        block.header.uncles_hash = Block::default().compute_uncles_hash();
+        assert!(matches!(verify(&block, &[]), Err(BlockError::InvalidUnclesHash { .. })));
+
+        assert_eq!(
+            verify(&block_with(vec![uncle.clone(), uncle.clone()]), &[]),
+            Err(BlockError::DuplicateUncle(uncle.hash()))
+        );
+        assert_eq!(
+            verify(&block_with(vec![uncle.clone(), uncle_of(&ancestry[2]), uncle_of(&ancestry[3])]), &[]),
+            Err(BlockError::TooManyUncles { max: 2, got: 3 })
+        );
+        assert_eq!(
+            verify(&block_with(vec![ancestry[2].clone()]), &[]),
+            Err(BlockError::UncleIsAncestor(ancestry[2].hash()))
+        );
+        assert_eq!(
+            verify(&block_with(vec![uncle.clone()]), &[uncle.hash()]),
+            Err(BlockError::UncleAlreadyIncluded(uncle.hash()))
+        );
+        // a brother of the block itself, and one generation too old
+        let brother = uncle_of(&ancestry[0]);
+        assert_eq!(
+            verify(&block_with(vec![brother]), &[]),
+            Err(BlockError::UncleOutOfBounds { min: 14, max: 19, got: 20 })
+        );
+        assert_eq!(
+            verify(&block_with(vec![uncle_of(&ancestry[7])]), &[]),
+            Err(BlockError::UncleOutOfBounds { min: 14, max: 19, got: 13 })
+        );
+
+        let mut orphan = uncle.clone();
+        orphan.parent_hash = 7.into();
+        assert_eq!(
+            verify(&block_with(vec![orphan]), &[]),
+            Err(BlockError::UncleParentNotInChain(7.into()))
+        );
+    }
+}
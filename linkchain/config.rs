@@ -0,0 +1,83 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use byteorder::{BigEndian, ByteOrder};
+use ethereum_types::{Address, U256};
+use lccore::Block;
+use lccore::Header;
+use rustc_hex::FromHex;
+use toml;
+use error::Error;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    pub genesis: GenesisConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenesisConfig {
+    pub coinbase: String,
+    pub difficulty: u64,
+    pub gas_limit: u64,
+    pub timestamp: u64,
+    pub extra_data: String,
+    pub nonce: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            genesis: GenesisConfig {
+                coinbase: format!("0x{:040x}", 0),
+                difficulty: 0x20000,
+                gas_limit: 0x47b760,
+                timestamp: 0,
+                extra_data: "0x".into(),
+                nonce: 0x42,
+            },
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut content = String::new();
+        fs::File::open(path)?.read_to_string(&mut content)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let content = toml::to_string(self)?;
+        fs::File::create(path)?.write_all(content.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl GenesisConfig {
+    pub fn block(&self) -> Result<Block, Error> {
+        let coinbase = from_hex(&self.coinbase)?;
+        if coinbase.len() != 20 {
+            return Err(Error::Config(format!("invalid coinbase {}", self.coinbase)));
+        }
+
+        let mut header = Header::new();
+        header.coinbase = Address::from(coinbase.as_slice());
+        header.difficulty = U256::from(self.difficulty);
+        header.gas_limit = U256::from(self.gas_limit);
+        header.timestamp = self.timestamp;
+        header.extra_data = from_hex(&self.extra_data)?;
+        header.nonce = vec![0; 8];
+        BigEndian::write_u64(&mut header.nonce, self.nonce);
+        Ok(Block {
+            header,
+            transactions: vec![],
+            uncles: vec![],
+        })
+    }
+}
+
+pub fn from_hex(s: &str) -> Result<Vec<u8>, Error> {
+    let s = s.trim_start_matches("0x");
+    s.from_hex()
+        .map_err(|e| Error::Config(format!("invalid hex {}: {}", s, e)))
+}
@@ -0,0 +1,59 @@
+use std::{fmt, io};
+use lccore::{BlockError, ChainError};
+use rlp::DecoderError;
+use toml;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Decoder(DecoderError),
+    Config(String),
+    Chain(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "io error: {}", e),
+            Error::Decoder(ref e) => write!(f, "rlp error: {}", e),
+            Error::Config(ref msg) => write!(f, "config error: {}", msg),
+            Error::Chain(ref msg) => write!(f, "chain error: {}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<DecoderError> for Error {
+    fn from(e: DecoderError) -> Self {
+        Error::Decoder(e)
+    }
+}
+
+impl From<ChainError> for Error {
+    fn from(e: ChainError) -> Self {
+        Error::Chain(e.to_string())
+    }
+}
+
+impl From<BlockError> for Error {
+    fn from(e: BlockError) -> Self {
+        Error::Chain(e.to_string())
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(e: toml::de::Error) -> Self {
+        Error::Config(e.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(e: toml::ser::Error) -> Self {
+        Error::Config(e.to_string())
+    }
+}
@@ -0,0 +1,436 @@
+extern crate byteorder;
+extern crate clap;
+extern crate ethereum_types;
+extern crate kvdb;
+extern crate lccore;
+extern crate rlp;
+extern crate rustc_hex;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
+
+#[cfg(test)]
+extern crate tempdir;
+
+mod config;
+mod error;
+
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use kvdb::LogDB;
+use lccore::blockchain::NUM_COLUMNS;
+use lccore::verification::VerificationParams;
+use lccore::{Block, BlockChain, BlockProducer, ChainError, Engine, InstantSeal, Schedule, TransactionPool};
+use rlp::{PayloadInfo, UntrustedRlp};
+use config::{from_hex, Config};
+use error::Error;
+
+const CONFIG_FILE: &str = "config.toml";
+const CHAIN_DIR: &str = "chain";
+
+struct Dirs {
+    data_dir: PathBuf,
+    config_file: PathBuf,
+}
+
+impl Dirs {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        let data_dir = match matches.value_of("datadir") {
+            Some(dir) => PathBuf::from(dir),
+            None => env::var_os("HOME")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join(".linkchain"),
+        };
+        let config_file = match matches.value_of("config") {
+            Some(file) => PathBuf::from(file),
+            None => data_dir.join(CONFIG_FILE),
+        };
+        Dirs {
+            data_dir,
+            config_file,
+        }
+    }
+
+    fn chain_dir(&self) -> PathBuf {
+        self.data_dir.join(CHAIN_DIR)
+    }
+}
+
+fn main() {
+    let matches = App::new("linkchain")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("simple ethereum in rust")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("datadir")
+                .long("datadir")
+                .short("d")
+                .value_name("DIR")
+                .help("Directory for chain data, defaults to $HOME/.linkchain")
+                .global(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .short("c")
+                .value_name("FILE")
+                .help("Config file, defaults to <datadir>/config.toml")
+                .global(true)
+                .takes_value(true),
+        )
+        .subcommand(SubCommand::with_name("init").about("Create the data dir and genesis block"))
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Run the node, sealing a block every period")
+                .arg(
+                    Arg::with_name("period")
+                        .long("period")
+                        .value_name("SECONDS")
+                        .help("Seconds between blocks, defaults to 5")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Import RLP encoded blocks from a file")
+                .arg(Arg::with_name("file").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Export the chain as RLP encoded blocks")
+                .arg(Arg::with_name("file").required(true))
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .value_name("NUMBER")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("NUMBER")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("inspect")
+                .about("Print a stored block, or decode a block given as hex RLP")
+                .arg(Arg::with_name("number").help("Block number, defaults to the best block"))
+                .arg(
+                    Arg::with_name("rlp")
+                        .long("rlp")
+                        .value_name("HEX")
+                        .takes_value(true)
+                        .conflicts_with("number"),
+                ),
+        )
+        .get_matches();
+
+    let (name, sub_matches) = matches.subcommand();
+    let sub_matches = sub_matches.expect("subcommand is required");
+    let dirs = Dirs::from_matches(sub_matches);
+    let result = match name {
+        "init" => init(&dirs),
+        "run" => run(&dirs, sub_matches),
+        "import" => import(&dirs, sub_matches),
+        "export" => export(&dirs, sub_matches),
+        "inspect" => inspect(&dirs, sub_matches),
+        _ => unreachable!(),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn load_config(dirs: &Dirs) -> Result<Config, Error> {
+    if dirs.config_file.exists() {
+        Config::load(&dirs.config_file)
+    } else {
+        Ok(Config::default())
+    }
+}
+
+fn open_chain(dirs: &Dirs, config: &Config) -> Result<BlockChain, Error> {
+    let dir = dirs.chain_dir();
+    if !dir.exists() {
+        return Err(Error::Chain(format!(
+            "no chain found at {}, run `linkchain init` first",
+            dir.display()
+        )));
+    }
+    let db = Arc::new(LogDB::open(&dir, NUM_COLUMNS)?);
+    Ok(BlockChain::new(db, &config.genesis.block()?)?)
+}
+
+fn init(dirs: &Dirs) -> Result<(), Error> {
+    fs::create_dir_all(&dirs.data_dir)?;
+    if !dirs.config_file.exists() {
+        Config::default().save(&dirs.config_file)?;
+        println!("wrote default config to {}", dirs.config_file.display());
+    }
+    let config = load_config(dirs)?;
+    let dir = dirs.chain_dir();
+    if dir.exists() {
+        return Err(Error::Chain(format!(
+            "chain already initialized at {}",
+            dir.display()
+        )));
+    }
+    fs::create_dir_all(&dir)?;
+    let chain = open_chain(dirs, &config)?;
+    println!(
+        "initialized chain at {}, genesis {:?}",
+        dirs.data_dir.display(),
+        chain.genesis_hash()
+    );
+    Ok(())
+}
+
+fn run(dirs: &Dirs, matches: &ArgMatches) -> Result<(), Error> {
+    let period = parse_period(matches.value_of("period"))?;
+    let config = load_config(dirs)?;
+    let mut chain = open_chain(dirs, &config)?;
+    let best = best_block(&chain)?;
+    println!(
+        "genesis {:?}, best block #{} {:?}",
+        chain.genesis_hash(),
+        best.header.number,
+        best.header.hash()
+    );
+
+    let engine: Arc<dyn Engine> = Arc::new(InstantSeal);
+    println!("sealing with {} every {} seconds", engine.name(), period);
+    let pool = TransactionPool::new(Schedule::default(), None);
+    let mut producer = BlockProducer::new(engine, VerificationParams::default(), pool, best);
+    loop {
+        thread::sleep(Duration::from_secs(period));
+        if let Some(block) = seal_block(&mut producer, &mut chain)? {
+            println!(
+                "sealed block #{} {:?} with {} transactions",
+                block.header.number,
+                block.header.hash(),
+                block.transactions.len()
+            );
+        }
+    }
+}
+
+/// Seals the pending transactions into a block and stores it in `chain`.
+/// Only the new best block stays in `producer`.
+fn seal_block(producer: &mut BlockProducer, chain: &mut BlockChain) -> Result<Option<Block>, Error> {
+    let block = match producer.seal_pending()? {
+        Some(block) => block.clone(),
+        None => return Ok(None),
+    };
+    chain.insert_block(&block)?;
+    producer.prune();
+    Ok(Some(block))
+}
+
+fn best_block(chain: &BlockChain) -> Result<Block, Error> {
+    let hash = chain.best_block().hash;
+    chain
+        .block(&hash)?
+        .ok_or_else(|| Error::Chain(format!("missing best block {:?}", hash)))
+}
+
+fn canonical_block(chain: &BlockChain, number: u64) -> Result<Option<Block>, Error> {
+    match chain.block_hash(number)? {
+        Some(hash) => Ok(chain.block(&hash)?),
+        None => Ok(None),
+    }
+}
+
+fn import(dirs: &Dirs, matches: &ArgMatches) -> Result<(), Error> {
+    let config = load_config(dirs)?;
+    let mut chain = open_chain(dirs, &config)?;
+    let mut bytes = Vec::new();
+    fs::File::open(matches.value_of("file").expect("file is required"))?
+        .read_to_end(&mut bytes)?;
+
+    let (mut imported, mut known) = (0, 0);
+    for block in decode_blocks(&bytes)? {
+        match chain.insert_block(&block) {
+            Ok(_) => imported += 1,
+            Err(ChainError::AlreadyInChain(_)) => known += 1,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    println!(
+        "imported {} blocks ({} already known), best block #{}",
+        imported,
+        known,
+        chain.best_block().number
+    );
+    Ok(())
+}
+
+fn export(dirs: &Dirs, matches: &ArgMatches) -> Result<(), Error> {
+    let config = load_config(dirs)?;
+    let chain = open_chain(dirs, &config)?;
+    let best = chain.best_block().number;
+    let from = parse_number(matches.value_of("from"), 0)?;
+    let to = parse_number(matches.value_of("to"), best)?.min(best);
+
+    let mut file = fs::File::create(matches.value_of("file").expect("file is required"))?;
+    let mut count = 0;
+    for number in from..=to {
+        let block = canonical_block(&chain, number)?
+            .ok_or_else(|| Error::Chain(format!("missing canonical block #{}", number)))?;
+        file.write_all(&rlp::encode(&block))?;
+        count += 1;
+    }
+    println!("exported {} blocks", count);
+    Ok(())
+}
+
+fn inspect(dirs: &Dirs, matches: &ArgMatches) -> Result<(), Error> {
+    if let Some(hex) = matches.value_of("rlp") {
+        let bytes = from_hex(hex)?;
+        let block: Block = UntrustedRlp::new(&bytes).as_val()?;
+        print_block(&block);
+        return Ok(());
+    }
+
+    let config = load_config(dirs)?;
+    let chain = open_chain(dirs, &config)?;
+    let best = chain.best_block().number;
+    let number = parse_number(matches.value_of("number"), best)?;
+    match canonical_block(&chain, number)? {
+        Some(block) => {
+            print_block(&block);
+            Ok(())
+        }
+        None => Err(Error::Chain(format!("unknown block #{}", number))),
+    }
+}
+
+/// Splits concatenated RLP blocks, as written by `export`.
+fn decode_blocks(bytes: &[u8]) -> Result<Vec<Block>, Error> {
+    let mut blocks = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let len = PayloadInfo::from(&bytes[offset..])?.total();
+        if offset + len > bytes.len() {
+            return Err(Error::Chain("truncated block data".into()));
+        }
+        blocks.push(UntrustedRlp::new(&bytes[offset..offset + len]).as_val()?);
+        offset += len;
+    }
+    Ok(blocks)
+}
+
+fn parse_period(value: Option<&str>) -> Result<u64, Error> {
+    match value {
+        Some(v) => match v.parse() {
+            Ok(0) | Err(_) => Err(Error::Config(format!("invalid period {}, expected seconds > 0", v))),
+            Ok(period) => Ok(period),
+        },
+        None => Ok(5),
+    }
+}
+
+fn parse_number(value: Option<&str>, default: u64) -> Result<u64, Error> {
+    match value {
+        Some(v) => v
+            .parse()
+            .map_err(|_| Error::Config(format!("invalid block number {}", v))),
+        None => Ok(default),
+    }
+}
+
+fn print_block(block: &Block) {
+    let header = &block.header;
+    println!("hash:              {:?}", header.hash());
+    println!("number:            {}", header.number);
+    println!("parent hash:       {:?}", header.parent_hash);
+    println!("coinbase:          {:?}", header.coinbase);
+    println!("state root:        {:?}", header.state_root);
+    println!("transactions root: {:?}", header.transactions_root);
+    println!("receipts root:     {:?}", header.receipts_root);
+    println!("difficulty:        {}", header.difficulty);
+    println!("gas limit:         {}", header.gas_limit);
+    println!("gas used:          {}", header.gas_used);
+    println!("timestamp:         {}", header.timestamp);
+    println!("transactions:      {}", block.transactions.len());
+    for tx in &block.transactions {
+        println!("  {:?}", tx.hash());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use lccore::verification::VerificationParams;
+    use lccore::{BlockProducer, ChainError, InstantSeal, Schedule, TransactionPool};
+    use rlp;
+    use tempdir::TempDir;
+    use config::Config;
+    use super::{best_block, canonical_block, decode_blocks, init, open_chain, parse_period, seal_block, Dirs, CONFIG_FILE};
+
+    #[test]
+    fn test_sealed_blocks_persist() {
+        let dir = TempDir::new("linkchain").unwrap();
+        let dirs = Dirs {
+            data_dir: dir.path().to_path_buf(),
+            config_file: dir.path().join(CONFIG_FILE),
+        };
+        init(&dirs).unwrap();
+        assert!(init(&dirs).is_err());
+
+        let config = Config::default();
+        let blocks = {
+            let mut chain = open_chain(&dirs, &config).unwrap();
+            let mut producer = BlockProducer::new(
+                Arc::new(InstantSeal),
+                VerificationParams::default(),
+                TransactionPool::new(Schedule::default(), None),
+                best_block(&chain).unwrap(),
+            );
+            let block1 = seal_block(&mut producer, &mut chain).unwrap().unwrap();
+            let block2 = seal_block(&mut producer, &mut chain).unwrap().unwrap();
+            assert_eq!(producer.block(1), None);
+            assert_eq!(producer.best_block(), &block2);
+            vec![block1, block2]
+        };
+
+        let mut chain = open_chain(&dirs, &config).unwrap();
+        assert_eq!(chain.best_block().number, 2);
+        assert_eq!(canonical_block(&chain, 1).unwrap().as_ref(), Some(&blocks[0]));
+        assert_eq!(best_block(&chain).unwrap(), blocks[1]);
+        assert!(matches!(chain.insert_block(&blocks[1]), Err(ChainError::AlreadyInChain(_))));
+    }
+
+    #[test]
+    fn test_parse_period() {
+        assert_eq!(parse_period(None).unwrap(), 5);
+        assert_eq!(parse_period(Some("2")).unwrap(), 2);
+        assert!(parse_period(Some("0")).is_err());
+        assert!(parse_period(Some("-1")).is_err());
+    }
+
+    #[test]
+    fn test_decode_concatenated_blocks() {
+        let genesis = Config::default().genesis.block().unwrap();
+        let mut block1 = genesis.clone();
+        block1.header.number = 1;
+        block1.header.parent_hash = genesis.header.hash();
+        let mut bytes = rlp::encode(&genesis).into_vec();
+        bytes.extend_from_slice(&rlp::encode(&block1));
+        assert_eq!(decode_blocks(&bytes).unwrap(), vec![genesis, block1]);
+        bytes.pop();
+        assert!(decode_blocks(&bytes).is_err());
+    }
+}
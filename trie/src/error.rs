@@ -0,0 +1,63 @@
+use std::error::Error as StdError;
+use std::fmt;
+use ethereum_types::H256;
+use rlp::DecoderError;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrieError {
+    /// The root node is not in the database.
+    InvalidStateRoot(H256),
+    /// A node below the root is not in the database.
+    IncompleteDatabase(H256),
+    /// A node that doesn't decode, identified by the hash of its encoding.
+    CorruptNode(H256, DecoderError),
+}
+
+impl fmt::Display for TrieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TrieError::InvalidStateRoot(ref root) => write!(f, "invalid state root {:?}", root),
+            TrieError::IncompleteDatabase(ref hash) => write!(f, "missing trie node {:?}", hash),
+            TrieError::CorruptNode(ref hash, ref e) => write!(f, "corrupt trie node {:?}: {}", hash, e),
+        }
+    }
+}
+
+impl StdError for TrieError {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ProofError {
+    /// The proof ends before reaching the node with this hash.
+    MissingNode(H256),
+    /// A proof node doesn't hash to the reference its parent holds.
+    MismatchedNode {
+        expected: H256,
+        got: H256,
+    },
+    InvalidNode(DecoderError),
+    /// The proof carries nodes past the end of the path.
+    UnusedNodes,
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProofError::MissingNode(ref hash) => write!(f, "proof is missing node {:?}", hash),
+            ProofError::MismatchedNode { ref expected, ref got } => write!(
+                f,
+                "mismatched proof node, expected {:?}, got {:?}",
+                expected, got
+            ),
+            ProofError::InvalidNode(ref e) => write!(f, "invalid proof node: {}", e),
+            ProofError::UnusedNodes => write!(f, "proof has unused nodes"),
+        }
+    }
+}
+
+impl StdError for ProofError {}
+
+impl From<DecoderError> for ProofError {
+    fn from(e: DecoderError) -> Self {
+        ProofError::InvalidNode(e)
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use bytes::Bytes;
+use ethereum_types::H256;
+use keccak_hash::keccak;
+
+/// Content addressed node storage. Values are keyed by their keccak hash and
+/// reference counted, so identical nodes shared by several paths survive
+/// until the last one is removed.
+pub trait HashDB {
+    fn get(&self, key: &H256) -> Option<Bytes>;
+
+    fn contains(&self, key: &H256) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Stores `value` and returns its hash.
+    fn insert(&mut self, value: &[u8]) -> H256;
+
+    /// Stores `value` under a key the caller has already hashed.
+    fn emplace(&mut self, key: H256, value: Bytes);
+
+    /// Drops one reference to `key`.
+    fn remove(&mut self, key: &H256);
+}
+
+/// In-memory `HashDB`. Reference counts may go negative when a node is
+/// removed before it is inserted, which lets a `MemoryDB` act as a pending
+/// change set on top of another database.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct MemoryDB {
+    data: HashMap<H256, (Bytes, i32)>,
+}
+
+impl MemoryDB {
+    pub fn new() -> Self {
+        MemoryDB::default()
+    }
+
+    /// Number of entries with a positive reference count.
+    pub fn len(&self) -> usize {
+        self.data.values().filter(|&&(_, rc)| rc > 0).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn refcount(&self, key: &H256) -> i32 {
+        self.data.get(key).map_or(0, |&(_, rc)| rc)
+    }
+
+    /// Forgets entries whose references have all been removed.
+    pub fn purge(&mut self) {
+        self.data.retain(|_, &mut (_, rc)| rc != 0);
+    }
+
+    /// Takes every entry with its reference count, leaving the database empty.
+    pub fn drain(&mut self) -> HashMap<H256, (Bytes, i32)> {
+        ::std::mem::take(&mut self.data)
+    }
+}
+
+impl HashDB for MemoryDB {
+    fn get(&self, key: &H256) -> Option<Bytes> {
+        match self.data.get(key) {
+            Some(&(ref value, rc)) if rc > 0 => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, value: &[u8]) -> H256 {
+        let key = keccak(value);
+        self.emplace(key, value.to_vec());
+        key
+    }
+
+    fn emplace(&mut self, key: H256, value: Bytes) {
+        match self.data.entry(key) {
+            Entry::Occupied(mut entry) => {
+                let &mut (ref mut old, ref mut rc) = entry.get_mut();
+                if *rc <= 0 {
+                    *old = value;
+                }
+                *rc += 1;
+            }
+            Entry::Vacant(entry) => {
+                entry.insert((value, 1));
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &H256) {
+        match self.data.entry(*key) {
+            Entry::Occupied(mut entry) => entry.get_mut().1 -= 1,
+            Entry::Vacant(entry) => {
+                entry.insert((Bytes::new(), -1));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HashDB, MemoryDB};
+
+    #[test]
+    fn test_refcount() {
+        let mut db = MemoryDB::new();
+        let key = db.insert(b"dog");
+        db.insert(b"dog");
+        db.remove(&key);
+        assert_eq!(db.get(&key), Some(b"dog".to_vec()));
+        db.remove(&key);
+        assert!(!db.contains(&key));
+        assert!(db.is_empty());
+    }
+
+    #[test]
+    fn test_remove_before_insert() {
+        let mut db = MemoryDB::new();
+        let key = ::keccak_hash::keccak(b"cat");
+        db.remove(&key);
+        assert_eq!(db.refcount(&key), -1);
+        db.insert(b"cat");
+        assert_eq!(db.refcount(&key), 0);
+        db.purge();
+        assert!(db.drain().is_empty());
+    }
+}
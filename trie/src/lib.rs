@@ -0,0 +1,24 @@
+//! Merkle Patricia Trie as used for Ethereum state, transactions and
+//! receipts, storing its nodes in a reference counted `HashDB`.
+
+extern crate bytes;
+extern crate ethereum_types;
+extern crate keccak_hash;
+extern crate rlp;
+
+#[cfg(test)]
+extern crate rustc_hex;
+
+mod error;
+mod hashdb;
+mod nibbles;
+mod node;
+mod ordered;
+mod proof;
+mod triedb;
+
+pub use error::{ProofError, TrieError};
+pub use hashdb::{HashDB, MemoryDB};
+pub use ordered::ordered_trie_root;
+pub use proof::verify_proof;
+pub use triedb::{TrieDB, TrieDBMut};
@@ -0,0 +1,77 @@
+//! Keys are walked a nibble at a time. On disk a nibble path is hex-prefix
+//! encoded: the first nibble holds the leaf and odd length flags, padded
+//! with a zero nibble when the path is even.
+
+pub fn from_key(key: &[u8]) -> Vec<u8> {
+    key.iter().flat_map(|b| vec![b >> 4, b & 0x0f]).collect()
+}
+
+/// Packs a whole number of nibbles back into bytes.
+pub fn to_key(nibbles: &[u8]) -> Vec<u8> {
+    nibbles.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect()
+}
+
+pub fn encode_path(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let odd = nibbles.len() % 2 == 1;
+    let flag = if leaf { 0x20 } else { 0 };
+    let mut out = Vec::with_capacity(nibbles.len() / 2 + 1);
+    let rest = if odd {
+        out.push(flag | 0x10 | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        out.push(flag);
+        nibbles
+    };
+    out.extend(to_key(rest));
+    out
+}
+
+/// Splits an encoded path into its nibbles and the leaf flag.
+pub fn decode_path(encoded: &[u8]) -> Option<(Vec<u8>, bool)> {
+    let (&first, rest) = encoded.split_first()?;
+    if first & 0xc0 != 0 {
+        return None;
+    }
+    let leaf = first & 0x20 != 0;
+    let mut nibbles = Vec::with_capacity(rest.len() * 2 + 1);
+    if first & 0x10 != 0 {
+        nibbles.push(first & 0x0f);
+    } else if first & 0x0f != 0 {
+        return None;
+    }
+    nibbles.extend(from_key(rest));
+    Some((nibbles, leaf))
+}
+
+pub fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|&(x, y)| x == y).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_path, encode_path};
+
+    #[test]
+    fn test_hex_prefix() {
+        // vectors from the yellow paper appendix and hexencodetest.json
+        let cases: Vec<(Vec<u8>, bool, Vec<u8>)> = vec![
+            (vec![1, 2, 3, 4, 5], false, vec![0x11, 0x23, 0x45]),
+            (vec![0, 1, 2, 3, 4, 5], false, vec![0x00, 0x01, 0x23, 0x45]),
+            (vec![0, 15, 1, 12, 11, 8], true, vec![0x20, 0x0f, 0x1c, 0xb8]),
+            (vec![15, 1, 12, 11, 8], true, vec![0x3f, 0x1c, 0xb8]),
+            (vec![], false, vec![0x00]),
+            (vec![], true, vec![0x20]),
+        ];
+        for (nibbles, leaf, encoded) in cases {
+            assert_eq!(encode_path(&nibbles, leaf), encoded);
+            assert_eq!(decode_path(&encoded), Some((nibbles, leaf)));
+        }
+    }
+
+    #[test]
+    fn test_invalid_prefix() {
+        assert_eq!(decode_path(&[]), None);
+        assert_eq!(decode_path(&[0x40]), None);
+        assert_eq!(decode_path(&[0x05, 0x12]), None);
+    }
+}
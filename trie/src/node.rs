@@ -0,0 +1,137 @@
+use bytes::Bytes;
+use ethereum_types::H256;
+use rlp::{DecoderError, RlpStream, UntrustedRlp};
+use nibbles::{decode_path, encode_path};
+
+/// Nodes whose encoding is shorter than a hash are embedded in their parent
+/// instead of being stored on their own.
+pub const MAX_INLINE_SIZE: usize = 32;
+
+/// How a parent points at a child.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChildRef {
+    Hash(H256),
+    /// The child's own encoding.
+    Inline(Bytes),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Empty,
+    Leaf(Vec<u8>, Bytes),
+    Extension(Vec<u8>, ChildRef),
+    Branch(Box<[Option<ChildRef>; 16]>, Option<Bytes>),
+}
+
+fn append_child(s: &mut RlpStream, child: &Option<ChildRef>) {
+    match *child {
+        Some(ChildRef::Hash(ref hash)) => {
+            s.append(hash);
+        }
+        Some(ChildRef::Inline(ref encoded)) => {
+            s.append_raw(encoded, 1);
+        }
+        None => {
+            s.append_empty_data();
+        }
+    }
+}
+
+fn decode_child(rlp: &UntrustedRlp) -> Result<Option<ChildRef>, DecoderError> {
+    if rlp.is_list() {
+        return Ok(Some(ChildRef::Inline(rlp.as_raw().to_vec())));
+    }
+    match rlp.data()?.len() {
+        0 => Ok(None),
+        32 => Ok(Some(ChildRef::Hash(rlp.as_val()?))),
+        _ => Err(DecoderError::Custom("invalid child reference")),
+    }
+}
+
+impl Node {
+    pub fn encoded(&self) -> Bytes {
+        let mut s = RlpStream::new();
+        match *self {
+            Node::Empty => {
+                s.append_empty_data();
+            }
+            Node::Leaf(ref path, ref value) => {
+                s.begin_list(2);
+                s.append(&encode_path(path, true));
+                s.append(value);
+            }
+            Node::Extension(ref path, ref child) => {
+                s.begin_list(2);
+                s.append(&encode_path(path, false));
+                append_child(&mut s, &Some(child.clone()));
+            }
+            Node::Branch(ref children, ref value) => {
+                s.begin_list(17);
+                for child in children.iter() {
+                    append_child(&mut s, child);
+                }
+                match *value {
+                    Some(ref value) => s.append(value),
+                    None => s.append_empty_data(),
+                };
+            }
+        }
+        s.out()
+    }
+
+    pub fn decode(encoded: &[u8]) -> Result<Node, DecoderError> {
+        let rlp = UntrustedRlp::new(encoded);
+        if !rlp.is_list() {
+            return if rlp.data()?.is_empty() {
+                Ok(Node::Empty)
+            } else {
+                Err(DecoderError::RlpExpectedToBeList)
+            };
+        }
+        match rlp.item_count()? {
+            2 => {
+                let (path, leaf) = decode_path(rlp.at(0)?.data()?)
+                    .ok_or(DecoderError::Custom("invalid hex-prefix path"))?;
+                if leaf {
+                    Ok(Node::Leaf(path, rlp.val_at(1)?))
+                } else {
+                    match decode_child(&rlp.at(1)?)? {
+                        Some(child) if !path.is_empty() => Ok(Node::Extension(path, child)),
+                        _ => Err(DecoderError::Custom("invalid extension node")),
+                    }
+                }
+            }
+            17 => {
+                let mut children: [Option<ChildRef>; 16] = Default::default();
+                for (i, child) in children.iter_mut().enumerate() {
+                    *child = decode_child(&rlp.at(i)?)?;
+                }
+                let value: Bytes = rlp.val_at(16)?;
+                Ok(Node::Branch(Box::new(children), if value.is_empty() { None } else { Some(value) }))
+            }
+            _ => Err(DecoderError::RlpIncorrectListLen),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChildRef, Node};
+
+    #[test]
+    fn test_node_round_trip() {
+        let leaf = Node::Leaf(vec![1, 2, 3], b"puppy".to_vec());
+        let mut children: [Option<ChildRef>; 16] = Default::default();
+        children[3] = Some(ChildRef::Inline(leaf.encoded()));
+        children[9] = Some(ChildRef::Hash(7.into()));
+        let nodes = vec![
+            Node::Empty,
+            leaf.clone(),
+            Node::Extension(vec![4], ChildRef::Inline(leaf.encoded())),
+            Node::Branch(Box::new(children), Some(b"verb".to_vec())),
+        ];
+        for node in nodes {
+            assert_eq!(Node::decode(&node.encoded()), Ok(node));
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use ethereum_types::H256;
+use rlp;
+use hashdb::MemoryDB;
+use triedb::TrieDBMut;
+
+/// Root of a trie mapping `rlp(index)` to each value, the layout of the
+/// transactions and receipts tries.
+pub fn ordered_trie_root<I, V>(values: I) -> H256
+where
+    I: IntoIterator<Item = V>,
+    V: AsRef<[u8]>,
+{
+    let mut db = MemoryDB::new();
+    let mut trie = TrieDBMut::new(&mut db);
+    for (index, value) in values.into_iter().enumerate() {
+        trie.insert(&rlp::encode(&index), value.as_ref())
+            .expect("nodes of a fresh in-memory trie are never missing");
+    }
+    *trie.root()
+}
+
+#[cfg(test)]
+mod tests {
+    use ethereum_types::H256;
+    use keccak_hash::KECCAK_NULL_RLP;
+    use super::ordered_trie_root;
+
+    #[test]
+    fn test_ordered_trie_root() {
+        assert_eq!(ordered_trie_root(Vec::<Vec<u8>>::new()), KECCAK_NULL_RLP);
+        // past 127 the keys grow to two bytes
+        let values: Vec<Vec<u8>> = (0..200u32).map(|i| vec![i as u8; 1 + i as usize % 40]).collect();
+        let root = ordered_trie_root(&values);
+        assert!(root != H256::zero());
+        assert_eq!(ordered_trie_root(values.iter()), root);
+        let mut swapped = values.clone();
+        swapped.swap(0, 1);
+        assert!(ordered_trie_root(&swapped) != root);
+    }
+}
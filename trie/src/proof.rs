@@ -0,0 +1,147 @@
+use bytes::Bytes;
+use ethereum_types::H256;
+use keccak_hash::{keccak, KECCAK_NULL_RLP};
+use rlp::{DecoderError, UntrustedRlp};
+use error::ProofError;
+use nibbles::{decode_path, from_key};
+
+/// Checks a proof from `get_proof` against `root` without a database,
+/// returning the value it shows for `key`, or `None` when it shows the key
+/// is absent.
+pub fn verify_proof(root: &H256, key: &[u8], proof: &[Bytes]) -> Result<Option<Bytes>, ProofError> {
+    let path = from_key(key);
+    let mut path = &path[..];
+    let mut nodes = proof.iter();
+    let mut expected = *root;
+    let value = 'hashed: loop {
+        if expected == KECCAK_NULL_RLP && nodes.len() == 0 {
+            break None;
+        }
+        let encoded = nodes.next().ok_or(ProofError::MissingNode(expected))?;
+        let got = keccak(encoded);
+        if got != expected {
+            return Err(ProofError::MismatchedNode { expected, got });
+        }
+        let mut node = UntrustedRlp::new(encoded);
+        // inline children are walked in place, a hashed one needs the next node
+        loop {
+            let child = match node.item_count()? {
+                2 => {
+                    let (rest, leaf) = decode_path(node.at(0)?.data()?)
+                        .ok_or(DecoderError::Custom("invalid hex-prefix path"))?;
+                    if leaf {
+                        break 'hashed if rest[..] == *path { Some(node.val_at(1)?) } else { None };
+                    }
+                    if !path.starts_with(&rest) {
+                        break 'hashed None;
+                    }
+                    path = &path[rest.len()..];
+                    node.at(1)?
+                }
+                17 => match path.split_first() {
+                    None => {
+                        let value: Bytes = node.val_at(16)?;
+                        break 'hashed if value.is_empty() { None } else { Some(value) };
+                    }
+                    Some((&index, tail)) => {
+                        path = tail;
+                        node.at(index as usize)?
+                    }
+                },
+                _ => return Err(DecoderError::RlpIncorrectListLen.into()),
+            };
+            if child.is_list() {
+                node = child;
+                continue;
+            }
+            match child.data()?.len() {
+                0 => break 'hashed None,
+                32 => {
+                    expected = child.as_val()?;
+                    continue 'hashed;
+                }
+                _ => return Err(DecoderError::Custom("invalid child reference").into()),
+            }
+        }
+    };
+    if nodes.len() != 0 {
+        return Err(ProofError::UnusedNodes);
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use keccak_hash::{keccak, KECCAK_NULL_RLP};
+    use error::ProofError;
+    use hashdb::MemoryDB;
+    use triedb::{TrieDB, TrieDBMut};
+    use super::verify_proof;
+
+    const KEYS: [&str; 6] = ["do", "dog", "doge", "horse", "dogglesworth", "d"];
+
+    fn value(key: &str) -> Vec<u8> {
+        // long enough that most nodes are stored by hash, "d" stays inline
+        if key == "d" {
+            b"x".to_vec()
+        } else {
+            format!("the value stored under {}", key).into_bytes()
+        }
+    }
+
+    fn trie(db: &mut MemoryDB) -> ::ethereum_types::H256 {
+        let mut trie = TrieDBMut::new(db);
+        for key in KEYS.iter() {
+            trie.insert(key.as_bytes(), &value(key)).unwrap();
+        }
+        *trie.root()
+    }
+
+    #[test]
+    fn test_verify_proof() {
+        let mut db = MemoryDB::new();
+        let root = trie(&mut db);
+        let trie = TrieDB::new(&db, root).unwrap();
+        for key in KEYS.iter() {
+            let proof = trie.get_proof(key.as_bytes()).unwrap();
+            assert_eq!(verify_proof(&root, key.as_bytes(), &proof), Ok(Some(value(key))));
+        }
+        for key in ["dogs", "cat", "", "horses", "doe"].iter() {
+            let proof = trie.get_proof(key.as_bytes()).unwrap();
+            assert_eq!(verify_proof(&root, key.as_bytes(), &proof), Ok(None));
+        }
+        assert_eq!(verify_proof(&KECCAK_NULL_RLP, b"dog", &[]), Ok(None));
+    }
+
+    #[test]
+    fn test_bad_proofs() {
+        let mut db = MemoryDB::new();
+        let root = trie(&mut db);
+        let proof = TrieDB::new(&db, root).unwrap().get_proof(b"doge").unwrap();
+        assert!(proof.len() > 2);
+
+        let mut truncated = proof.clone();
+        let last = truncated.pop().unwrap();
+        assert_eq!(verify_proof(&root, b"doge", &truncated), Err(ProofError::MissingNode(keccak(&last))));
+
+        let mut tampered = proof.clone();
+        tampered[1][3] ^= 1;
+        assert_eq!(
+            verify_proof(&root, b"doge", &tampered),
+            Err(ProofError::MismatchedNode {
+                expected: keccak(&proof[1]),
+                got: keccak(&tampered[1]),
+            })
+        );
+
+        let mut padded = proof.clone();
+        padded.push(proof[0].clone());
+        assert_eq!(verify_proof(&root, b"doge", &padded), Err(ProofError::UnusedNodes));
+
+        assert_eq!(verify_proof(&root, b"doge", &[]), Err(ProofError::MissingNode(root)));
+        assert!(matches!(
+            verify_proof(&keccak(b"\x01"), b"doge", &[b"\x01".to_vec()]),
+            Err(ProofError::InvalidNode(_))
+        ));
+    }
+}
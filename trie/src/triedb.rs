@@ -0,0 +1,449 @@
+use bytes::Bytes;
+use ethereum_types::H256;
+use keccak_hash::{keccak, KECCAK_NULL_RLP};
+use error::TrieError;
+use hashdb::HashDB;
+use nibbles::{common_prefix, from_key};
+use node::{ChildRef, Node, MAX_INLINE_SIZE};
+
+fn decode(encoded: &[u8]) -> Result<Node, TrieError> {
+    Node::decode(encoded).map_err(|e| TrieError::CorruptNode(keccak(encoded), e))
+}
+
+fn resolve(db: &dyn HashDB, child: &ChildRef, record: &mut dyn FnMut(&[u8])) -> Result<Node, TrieError> {
+    match *child {
+        ChildRef::Hash(ref hash) => {
+            let encoded = db.get(hash).ok_or(TrieError::IncompleteDatabase(*hash))?;
+            record(&encoded);
+            decode(&encoded)
+        }
+        ChildRef::Inline(ref encoded) => decode(encoded),
+    }
+}
+
+fn root_node(db: &dyn HashDB, root: &H256, record: &mut dyn FnMut(&[u8])) -> Result<Node, TrieError> {
+    if *root == KECCAK_NULL_RLP {
+        return Ok(Node::Empty);
+    }
+    let encoded = db.get(root).ok_or(TrieError::InvalidStateRoot(*root))?;
+    record(&encoded);
+    decode(&encoded)
+}
+
+/// Walks to `key`, passing every node stored by hash along the way to
+/// `record`.
+fn lookup(db: &dyn HashDB, root: &H256, key: &[u8], record: &mut dyn FnMut(&[u8])) -> Result<Option<Bytes>, TrieError> {
+    let path = from_key(key);
+    let mut path = &path[..];
+    let mut node = root_node(db, root, record)?;
+    loop {
+        let child = match node {
+            Node::Empty => return Ok(None),
+            Node::Leaf(ref rest, ref value) => return Ok(if rest[..] == *path { Some(value.clone()) } else { None }),
+            Node::Extension(ref rest, ref child) => {
+                if !path.starts_with(rest) {
+                    return Ok(None);
+                }
+                path = &path[rest.len()..];
+                resolve(db, child, record)?
+            }
+            Node::Branch(ref children, ref value) => match path.split_first() {
+                None => return Ok(value.clone()),
+                Some((&index, tail)) => match children[index as usize] {
+                    Some(ref child) => {
+                        path = tail;
+                        resolve(db, child, record)?
+                    }
+                    None => return Ok(None),
+                },
+            },
+        };
+        node = child;
+    }
+}
+
+fn prove(db: &dyn HashDB, root: &H256, key: &[u8]) -> Result<Vec<Bytes>, TrieError> {
+    let mut proof = vec![];
+    lookup(db, root, key, &mut |node| proof.push(node.to_vec()))?;
+    Ok(proof)
+}
+
+/// Read-only view of a trie.
+pub struct TrieDB<'db> {
+    db: &'db dyn HashDB,
+    root: H256,
+}
+
+impl<'db> TrieDB<'db> {
+    pub fn new(db: &'db dyn HashDB, root: H256) -> Result<Self, TrieError> {
+        if root != KECCAK_NULL_RLP && !db.contains(&root) {
+            return Err(TrieError::InvalidStateRoot(root));
+        }
+        Ok(TrieDB { db, root })
+    }
+
+    pub fn root(&self) -> &H256 {
+        &self.root
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<Bytes>, TrieError> {
+        lookup(self.db, &self.root, key, &mut |_| ())
+    }
+
+    /// The nodes on the path to `key`, root first, enough for `verify_proof`
+    /// to show the key's value or its absence.
+    pub fn get_proof(&self, key: &[u8]) -> Result<Vec<Bytes>, TrieError> {
+        prove(self.db, &self.root, key)
+    }
+
+    pub fn contains(&self, key: &[u8]) -> Result<bool, TrieError> {
+        self.get(key).map(|value| value.is_some())
+    }
+}
+
+/// Mutable trie. Every change is written through to the database: replaced
+/// nodes are removed, new ones inserted, and the root node is always stored
+/// by hash even when it is small enough to be inlined.
+pub struct TrieDBMut<'db> {
+    db: &'db mut dyn HashDB,
+    root: H256,
+}
+
+impl<'db> TrieDBMut<'db> {
+    /// A new, empty trie.
+    pub fn new(db: &'db mut dyn HashDB) -> Self {
+        TrieDBMut {
+            db,
+            root: KECCAK_NULL_RLP,
+        }
+    }
+
+    pub fn from_existing(db: &'db mut dyn HashDB, root: H256) -> Result<Self, TrieError> {
+        if root != KECCAK_NULL_RLP && !db.contains(&root) {
+            return Err(TrieError::InvalidStateRoot(root));
+        }
+        Ok(TrieDBMut { db, root })
+    }
+
+    pub fn root(&self) -> &H256 {
+        &self.root
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root == KECCAK_NULL_RLP
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<Bytes>, TrieError> {
+        lookup(&*self.db, &self.root, key, &mut |_| ())
+    }
+
+    pub fn get_proof(&self, key: &[u8]) -> Result<Vec<Bytes>, TrieError> {
+        prove(&*self.db, &self.root, key)
+    }
+
+    pub fn contains(&self, key: &[u8]) -> Result<bool, TrieError> {
+        self.get(key).map(|value| value.is_some())
+    }
+
+    /// Inserting an empty value removes the key.
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<(), TrieError> {
+        if value.is_empty() {
+            return self.remove(key);
+        }
+        let root = self.take_root()?;
+        let root = self.insert_at(root, &from_key(key), value.to_vec())?;
+        self.set_root(root);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Result<(), TrieError> {
+        let root = self.take_root()?;
+        let root = self.remove_at(root, &from_key(key))?;
+        self.set_root(root);
+        Ok(())
+    }
+
+    fn take_root(&mut self) -> Result<Node, TrieError> {
+        let node = root_node(&*self.db, &self.root, &mut |_| ())?;
+        if self.root != KECCAK_NULL_RLP {
+            self.db.remove(&self.root);
+        }
+        Ok(node)
+    }
+
+    fn set_root(&mut self, node: Node) {
+        self.root = match node {
+            Node::Empty => KECCAK_NULL_RLP,
+            node => self.db.insert(&node.encoded()),
+        };
+    }
+
+    /// Loads a child the caller is about to replace, dropping its reference.
+    fn take(&mut self, child: ChildRef) -> Result<Node, TrieError> {
+        let node = resolve(&*self.db, &child, &mut |_| ())?;
+        if let ChildRef::Hash(ref hash) = child {
+            self.db.remove(hash);
+        }
+        Ok(node)
+    }
+
+    fn store(&mut self, node: Node) -> Option<ChildRef> {
+        if node == Node::Empty {
+            return None;
+        }
+        let encoded = node.encoded();
+        if encoded.len() < MAX_INLINE_SIZE {
+            Some(ChildRef::Inline(encoded))
+        } else {
+            Some(ChildRef::Hash(self.db.insert(&encoded)))
+        }
+    }
+
+    /// Puts `node` under an extension of `prefix`, merging it with the path
+    /// of a leaf or extension.
+    fn extend(&mut self, prefix: &[u8], node: Node) -> Node {
+        if prefix.is_empty() {
+            return node;
+        }
+        let join = |path: Vec<u8>| prefix.iter().chain(path.iter()).cloned().collect();
+        match node {
+            Node::Empty => Node::Empty,
+            Node::Leaf(path, value) => Node::Leaf(join(path), value),
+            Node::Extension(path, child) => Node::Extension(join(path), child),
+            branch => {
+                let child = self.store(branch).expect("branch is not empty");
+                Node::Extension(prefix.to_vec(), child)
+            }
+        }
+    }
+
+    fn insert_at(&mut self, node: Node, path: &[u8], value: Bytes) -> Result<Node, TrieError> {
+        match node {
+            Node::Empty => Ok(Node::Leaf(path.to_vec(), value)),
+            Node::Leaf(rest, old) => {
+                if rest[..] == *path {
+                    return Ok(Node::Leaf(rest, value));
+                }
+                let common = common_prefix(&rest, path);
+                let branch = Node::Branch(Box::default(), None);
+                let branch = self.insert_at(branch, &rest[common..], old)?;
+                let branch = self.insert_at(branch, &path[common..], value)?;
+                Ok(self.extend(&path[..common], branch))
+            }
+            Node::Extension(rest, child) => {
+                let common = common_prefix(&rest, path);
+                if common == rest.len() {
+                    let child = self.take(child)?;
+                    let child = self.insert_at(child, &path[common..], value)?;
+                    let child = self.store(child).expect("inserted node is not empty");
+                    return Ok(Node::Extension(rest, child));
+                }
+                let mut children: Box<[Option<ChildRef>; 16]> = Box::default();
+                children[rest[common] as usize] = if common + 1 == rest.len() {
+                    Some(child)
+                } else {
+                    self.store(Node::Extension(rest[common + 1..].to_vec(), child))
+                };
+                let branch = self.insert_at(Node::Branch(children, None), &path[common..], value)?;
+                Ok(self.extend(&path[..common], branch))
+            }
+            Node::Branch(mut children, old) => match path.split_first() {
+                None => Ok(Node::Branch(children, Some(value))),
+                Some((&index, tail)) => {
+                    let child = match children[index as usize].take() {
+                        Some(child) => self.take(child)?,
+                        None => Node::Empty,
+                    };
+                    let child = self.insert_at(child, tail, value)?;
+                    children[index as usize] = self.store(child);
+                    Ok(Node::Branch(children, old))
+                }
+            },
+        }
+    }
+
+    fn remove_at(&mut self, node: Node, path: &[u8]) -> Result<Node, TrieError> {
+        match node {
+            Node::Empty => Ok(Node::Empty),
+            Node::Leaf(rest, value) => Ok(if rest[..] == *path { Node::Empty } else { Node::Leaf(rest, value) }),
+            Node::Extension(rest, child) => {
+                if !path.starts_with(&rest) {
+                    return Ok(Node::Extension(rest, child));
+                }
+                let child = self.take(child)?;
+                let child = self.remove_at(child, &path[rest.len()..])?;
+                Ok(self.extend(&rest, child))
+            }
+            Node::Branch(mut children, mut value) => {
+                match path.split_first() {
+                    None => value = None,
+                    Some((&index, tail)) => {
+                        if let Some(child) = children[index as usize].take() {
+                            let child = self.take(child)?;
+                            let child = self.remove_at(child, tail)?;
+                            children[index as usize] = self.store(child);
+                        }
+                    }
+                }
+                self.fix_branch(children, value)
+            }
+        }
+    }
+
+    /// A branch left with a single entry collapses into a leaf or into its
+    /// only child.
+    fn fix_branch(&mut self, mut children: Box<[Option<ChildRef>; 16]>, value: Option<Bytes>) -> Result<Node, TrieError> {
+        let mut used = children.iter().enumerate().filter(|&(_, child)| child.is_some()).map(|(i, _)| i);
+        let (first, second) = (used.next(), used.next());
+        match (first, second, value) {
+            (None, _, None) => Ok(Node::Empty),
+            (None, _, Some(value)) => Ok(Node::Leaf(vec![], value)),
+            (Some(index), None, None) => {
+                let child = children[index].take().expect("index of a used child");
+                let child = self.take(child)?;
+                Ok(self.extend(&[index as u8], child))
+            }
+            (_, _, value) => Ok(Node::Branch(children, value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethereum_types::H256;
+    use keccak_hash::KECCAK_NULL_RLP;
+    use rustc_hex::FromHex;
+    use error::TrieError;
+    use hashdb::{HashDB, MemoryDB};
+    use super::{TrieDB, TrieDBMut};
+
+    fn bytes(s: &str) -> Vec<u8> {
+        match s.strip_prefix("0x") {
+            Some(hex) => hex.from_hex().unwrap(),
+            None => s.as_bytes().to_vec(),
+        }
+    }
+
+    fn root_of(pairs: &[(&str, &str)]) -> H256 {
+        let mut db = MemoryDB::new();
+        let mut trie = TrieDBMut::new(&mut db);
+        for &(key, value) in pairs {
+            trie.insert(&bytes(key), &bytes(value)).unwrap();
+        }
+        *trie.root()
+    }
+
+    #[test]
+    fn test_official_vectors() {
+        // trietest.json and trieanyorder.json from ethereum/tests
+        let cases: Vec<(Vec<(&str, &str)>, &str)> = vec![
+            (vec![], "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"),
+            (
+                vec![
+                    ("do", "verb"),
+                    ("ether", "wookiedoo"),
+                    ("horse", "stallion"),
+                    ("shaman", "horse"),
+                    ("doge", "coin"),
+                    ("ether", ""),
+                    ("dog", "puppy"),
+                    ("shaman", ""),
+                ],
+                "5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84",
+            ),
+            (
+                vec![("doe", "reindeer"), ("dog", "puppy"), ("dogglesworth", "cat")],
+                "8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3",
+            ),
+            (
+                vec![("foo", "bar"), ("food", "bass")],
+                "17beaa1648bafa633cda809c90c04af50fc8aed3cb40d16efbddee6fdf63c4c3",
+            ),
+            (
+                vec![("be", "e"), ("dog", "puppy"), ("bed", "d")],
+                "3f67c7a47520f79faa29255d2d3c084a7a6df0453116ed7232ff10277a8be68b",
+            ),
+            (
+                vec![("test", "test"), ("te", "testy")],
+                "8452568af70d8d140f58d941338542f645fcca50094b20f3c3d8c3df49337928",
+            ),
+            (
+                vec![("0x0045", "0x0123456789"), ("0x4500", "0x9876543210")],
+                "285505fcabe84badc8aa310e2aae17eddc7d120aabec8a476902c8184b3a3503",
+            ),
+        ];
+        for (pairs, root) in cases {
+            assert_eq!(root_of(&pairs), H256::from(root), "{:?}", pairs);
+            let mut reversed = pairs.clone();
+            reversed.reverse();
+            if !pairs.iter().any(|&(_, value)| value.is_empty()) {
+                assert_eq!(root_of(&reversed), H256::from(root), "{:?}", reversed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_and_remove() {
+        let keys = ["do", "dog", "doge", "horse", "dogglesworth", "d", ""];
+        let mut db = MemoryDB::new();
+        let root = {
+            let mut trie = TrieDBMut::new(&mut db);
+            for key in keys.iter() {
+                trie.insert(key.as_bytes(), format!("value of {}", key).as_bytes()).unwrap();
+            }
+            *trie.root()
+        };
+        {
+            let trie = TrieDB::new(&db, root).unwrap();
+            for key in keys.iter() {
+                assert_eq!(trie.get(key.as_bytes()).unwrap(), Some(format!("value of {}", key).into_bytes()));
+            }
+            assert_eq!(trie.get(b"doges").unwrap(), None);
+            assert_eq!(trie.get(b"horses").unwrap(), None);
+        }
+
+        let mut trie = TrieDBMut::from_existing(&mut db, root).unwrap();
+        for (i, key) in keys.iter().enumerate() {
+            trie.remove(key.as_bytes()).unwrap();
+            assert!(!trie.contains(key.as_bytes()).unwrap());
+            for rest in keys[i + 1..].iter() {
+                assert!(trie.contains(rest.as_bytes()).unwrap());
+            }
+        }
+        assert_eq!(*trie.root(), KECCAK_NULL_RLP);
+        assert!(db.is_empty());
+    }
+
+    #[test]
+    fn test_removal_restores_root() {
+        let mut db = MemoryDB::new();
+        let mut trie = TrieDBMut::new(&mut db);
+        trie.insert(b"doe", b"reindeer").unwrap();
+        trie.insert(b"dog", b"puppy").unwrap();
+        let root = *trie.root();
+        trie.insert(b"dogglesworth", b"cat").unwrap();
+        trie.remove(b"dogglesworth").unwrap();
+        trie.remove(b"unknown").unwrap();
+        assert_eq!(*trie.root(), root);
+    }
+
+    #[test]
+    fn test_missing_nodes() {
+        let mut db = MemoryDB::new();
+        let unknown = H256::from(1);
+        assert_eq!(TrieDB::new(&db, unknown).err(), Some(TrieError::InvalidStateRoot(unknown)));
+
+        let root = {
+            let mut trie = TrieDBMut::new(&mut db);
+            for i in 0..20u8 {
+                trie.insert(&[i], &[i; 40]).unwrap();
+            }
+            *trie.root()
+        };
+        let leaf = ::keccak_hash::keccak(::rlp::encode_list::<Vec<u8>, _>(&[vec![0x20], vec![3; 40]]));
+        db.remove(&leaf);
+        let trie = TrieDB::new(&db, root).unwrap();
+        assert_eq!(trie.get(&[3]), Err(TrieError::IncompleteDatabase(leaf)));
+        assert_eq!(trie.get(&[4]).unwrap(), Some(vec![4; 40]));
+    }
+}
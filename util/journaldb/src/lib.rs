@@ -0,0 +1,380 @@
+//! Trie node storage over a `KeyValueDB` that journals the nodes each block
+//! inserts and removes, so that old state can be pruned once it is out of
+//! reach of reorganisations.
+//!
+//! Nodes are stored with a reference count, `count: u32 | node` with the
+//! count little endian. The journal and the latest era live in a column of
+//! their own. Inserts take effect as soon as their block is committed. In
+//! `Recent` mode removals wait in the journal until the block becomes final
+//! `history` blocks later: then the canonical block's removals are applied
+//! and any sibling's inserts are undone.
+
+extern crate bytes;
+extern crate ethereum_types;
+extern crate kvdb;
+extern crate rlp;
+extern crate trie;
+
+#[cfg(test)]
+extern crate keccak_hash;
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::Arc;
+use bytes::Bytes;
+use ethereum_types::H256;
+use kvdb::{DBTransaction, KeyValueDB};
+use rlp::{RlpStream, UntrustedRlp};
+use trie::{HashDB, MemoryDB};
+
+const JOURNAL_PREFIX: &[u8] = b"journal";
+const LATEST_ERA_KEY: &[u8] = b"latest-era";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Keep every node forever.
+    Archive,
+    /// Keep the state of the last `history` blocks.
+    Recent { history: u64 },
+}
+
+/// What one block did to the node set, a hash appears once per reference.
+#[derive(Debug, Clone, PartialEq)]
+struct JournalEntry {
+    id: H256,
+    inserts: Vec<H256>,
+    removes: Vec<H256>,
+}
+
+impl JournalEntry {
+    fn encoded(&self) -> Bytes {
+        let mut s = RlpStream::new_list(3);
+        s.append(&self.id);
+        s.append_list(&self.inserts);
+        s.append_list(&self.removes);
+        s.out()
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let rlp = UntrustedRlp::new(bytes);
+        let decode = || -> Result<JournalEntry, rlp::DecoderError> {
+            Ok(JournalEntry {
+                id: rlp.val_at(0)?,
+                inserts: rlp.list_at(1)?,
+                removes: rlp.list_at(2)?,
+            })
+        };
+        decode().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("corrupt journal entry: {}", e)))
+    }
+}
+
+fn journal_key(era: u64, index: usize) -> Vec<u8> {
+    let mut key = JOURNAL_PREFIX.to_vec();
+    key.extend_from_slice(&era.to_be_bytes());
+    key.extend_from_slice(&(index as u32).to_be_bytes());
+    key
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn decode_node(bytes: &[u8]) -> io::Result<(u32, &[u8])> {
+    if bytes.len() < 4 {
+        return Err(invalid_data("stored node is missing its reference count"));
+    }
+    let mut count = [0u8; 4];
+    count.copy_from_slice(&bytes[..4]);
+    Ok((u32::from_le_bytes(count), &bytes[4..]))
+}
+
+fn decode_era(bytes: &[u8]) -> io::Result<u64> {
+    if bytes.len() != 8 {
+        return Err(invalid_data("corrupt journal era"));
+    }
+    let mut era = [0u8; 8];
+    era.copy_from_slice(bytes);
+    Ok(u64::from_be_bytes(era))
+}
+
+fn encode_node(count: u32, node: &[u8]) -> Bytes {
+    let mut bytes = count.to_le_bytes().to_vec();
+    bytes.extend_from_slice(node);
+    bytes
+}
+
+pub struct JournalDB {
+    backing: Arc<dyn KeyValueDB>,
+    col: u32,
+    journal_col: u32,
+    algorithm: Algorithm,
+    /// Changes of the block being built.
+    overlay: MemoryDB,
+    /// Blocks not yet final, by number.
+    journal: BTreeMap<u64, Vec<JournalEntry>>,
+    latest_era: Option<u64>,
+}
+
+impl JournalDB {
+    /// Opens the nodes kept in column `col` of `backing`, with their journal
+    /// in `journal_col`.
+    pub fn new(backing: Arc<dyn KeyValueDB>, col: u32, journal_col: u32, algorithm: Algorithm) -> io::Result<Self> {
+        let mut journal: BTreeMap<u64, Vec<JournalEntry>> = BTreeMap::new();
+        for (key, value) in backing.iter_with_prefix(journal_col, JOURNAL_PREFIX)? {
+            let era = key.get(JOURNAL_PREFIX.len()..JOURNAL_PREFIX.len() + 8)
+                .ok_or_else(|| invalid_data("corrupt journal key"))?;
+            journal
+                .entry(decode_era(era)?)
+                .or_default()
+                .push(JournalEntry::decode(&value)?);
+        }
+        let latest_era = match backing.get(journal_col, LATEST_ERA_KEY)? {
+            Some(bytes) => Some(decode_era(&bytes)?),
+            None => None,
+        };
+        Ok(JournalDB {
+            backing,
+            col,
+            journal_col,
+            algorithm,
+            overlay: MemoryDB::new(),
+            journal,
+            latest_era,
+        })
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Number of the last committed block.
+    pub fn latest_era(&self) -> Option<u64> {
+        self.latest_era
+    }
+
+    /// The block a `Recent` database finalizes when committing block `now`.
+    pub fn finalized_era(&self, now: u64) -> Option<u64> {
+        match self.algorithm {
+            Algorithm::Archive => None,
+            Algorithm::Recent { history } => now.checked_sub(history),
+        }
+    }
+
+    fn stored_node(&self, key: &H256) -> io::Result<Option<(u32, Bytes)>> {
+        match self.backing.get(self.col, key)? {
+            Some(bytes) => decode_node(&bytes).map(|(count, node)| Some((count, node.to_vec()))),
+            None => Ok(None),
+        }
+    }
+
+    /// Moves the pending changes into `batch` as block `now` with hash `id`.
+    /// In `Recent` mode `end` names the canonical block at
+    /// `finalized_era(now)`, whose journal is then applied.
+    ///
+    /// `batch` must be written before the next commit, reference counts are
+    /// read back from the database.
+    pub fn commit(&mut self, batch: &mut DBTransaction, now: u64, id: &H256, end: Option<(u64, H256)>) -> io::Result<()> {
+        let mut deltas: HashMap<H256, (Option<Bytes>, i64)> = HashMap::new();
+        let mut entry = JournalEntry {
+            id: *id,
+            inserts: vec![],
+            removes: vec![],
+        };
+        for (key, (value, count)) in self.overlay.drain() {
+            if count > 0 {
+                deltas.insert(key, (Some(value), i64::from(count)));
+                entry.inserts.extend((0..count).map(|_| key));
+            } else if count < 0 {
+                entry.removes.extend((0..-count).map(|_| key));
+            }
+        }
+
+        if let Algorithm::Recent { .. } = self.algorithm {
+            let era_entries = self.journal.entry(now).or_default();
+            batch.put(self.journal_col, &journal_key(now, era_entries.len()), &entry.encoded());
+            era_entries.push(entry);
+
+            if let Some((era, canonical)) = end {
+                for (index, entry) in self.journal.remove(&era).unwrap_or_default().into_iter().enumerate() {
+                    batch.delete(self.journal_col, &journal_key(era, index));
+                    // the canonical block's removals become final, a sibling's
+                    // inserts are dropped with it
+                    let dropped = if entry.id == canonical { entry.removes } else { entry.inserts };
+                    for key in dropped {
+                        deltas.entry(key).or_insert((None, 0)).1 -= 1;
+                    }
+                }
+            }
+        }
+
+        for (key, (value, delta)) in deltas {
+            let (count, node) = match self.stored_node(&key)? {
+                Some((count, node)) => (i64::from(count), node),
+                None => match value {
+                    Some(value) => (0, value),
+                    None => continue,
+                },
+            };
+            let count = count + delta;
+            if count > 0 {
+                batch.put(self.col, &key, &encode_node(count as u32, &node));
+            } else {
+                batch.delete(self.col, &key);
+            }
+        }
+
+        batch.put(self.journal_col, LATEST_ERA_KEY, &now.to_be_bytes());
+        self.latest_era = Some(now);
+        Ok(())
+    }
+}
+
+impl HashDB for JournalDB {
+    fn get(&self, key: &H256) -> Option<Bytes> {
+        // pending removals only take effect on commit. A read error reads as a
+        // missing node, the trie reports it as such
+        self.overlay
+            .get(key)
+            .or_else(|| self.stored_node(key).ok().and_then(|node| node).map(|(_, node)| node))
+    }
+
+    fn insert(&mut self, value: &[u8]) -> H256 {
+        self.overlay.insert(value)
+    }
+
+    fn emplace(&mut self, key: H256, value: Bytes) {
+        self.overlay.emplace(key, value)
+    }
+
+    fn remove(&mut self, key: &H256) {
+        self.overlay.remove(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::Arc;
+    use ethereum_types::H256;
+    use keccak_hash::keccak;
+    use kvdb::{DBTransaction, InMemory, KeyValueDB};
+    use trie::{HashDB, TrieDB, TrieDBMut};
+    use super::{Algorithm, JournalDB};
+
+    fn commit(jdb: &mut JournalDB, now: u64, id: H256, end: Option<(u64, H256)>) {
+        let mut batch = DBTransaction::new();
+        jdb.commit(&mut batch, now, &id, end).unwrap();
+        jdb.backing.write(batch).unwrap();
+    }
+
+    fn recent(backing: &Arc<InMemory>, history: u64) -> JournalDB {
+        JournalDB::new(backing.clone(), 0, 1, Algorithm::Recent { history }).unwrap()
+    }
+
+    #[test]
+    fn test_archive_keeps_everything() {
+        let mut jdb = JournalDB::new(Arc::new(InMemory::new(2)), 0, 1, Algorithm::Archive).unwrap();
+        let dog = jdb.insert(b"dog");
+        commit(&mut jdb, 0, keccak(b"0"), None);
+        jdb.remove(&dog);
+        commit(&mut jdb, 1, keccak(b"1"), None);
+        commit(&mut jdb, 2, keccak(b"2"), None);
+        assert!(jdb.contains(&dog));
+    }
+
+    #[test]
+    fn test_recent_prunes_after_history() {
+        let backing = Arc::new(InMemory::new(2));
+        let mut jdb = recent(&backing, 1);
+        let dog = jdb.insert(b"dog");
+        let cat = jdb.insert(b"cat");
+        commit(&mut jdb, 0, keccak(b"0"), None);
+
+        // dog is removed by block 1 but also inserted again by block 2
+        jdb.remove(&dog);
+        jdb.remove(&cat);
+        commit(&mut jdb, 1, keccak(b"1"), Some((0, keccak(b"0"))));
+        assert!(jdb.contains(&dog) && jdb.contains(&cat));
+        jdb.insert(b"dog");
+        commit(&mut jdb, 2, keccak(b"2"), Some((1, keccak(b"1"))));
+        assert!(jdb.contains(&dog));
+        assert!(!jdb.contains(&cat));
+
+        // nothing is left once every journal is applied
+        jdb.remove(&dog);
+        commit(&mut jdb, 3, keccak(b"3"), Some((2, keccak(b"2"))));
+        commit(&mut jdb, 4, keccak(b"4"), Some((3, keccak(b"3"))));
+        commit(&mut jdb, 5, keccak(b"5"), Some((4, keccak(b"4"))));
+        assert!(!jdb.contains(&dog));
+        assert_eq!(backing.iter(0).unwrap().count(), 0);
+        let stored: Vec<_> = backing.iter(1).unwrap().map(|(key, _)| key).collect();
+        assert_eq!(stored.len(), 2, "the journal of block 5 and the latest era");
+    }
+
+    #[test]
+    fn test_corrupt_node_fails_commit() {
+        let backing = Arc::new(InMemory::new(2));
+        let mut jdb = recent(&backing, 1);
+        let dog = jdb.insert(b"dog");
+        commit(&mut jdb, 0, keccak(b"0"), None);
+
+        let mut batch = DBTransaction::new();
+        batch.put(0, &dog, &[1, 0]);
+        backing.write(batch).unwrap();
+        jdb.insert(b"dog");
+        let mut batch = DBTransaction::new();
+        let err = jdb.commit(&mut batch, 1, &keccak(b"1"), Some((0, keccak(b"0")))).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(!jdb.contains(&dog));
+    }
+
+    #[test]
+    fn test_fork_inserts_are_undone() {
+        let backing = Arc::new(InMemory::new(2));
+        let mut jdb = recent(&backing, 1);
+        commit(&mut jdb, 0, keccak(b"0"), None);
+
+        let shared = jdb.insert(b"shared");
+        let a = jdb.insert(b"only in a");
+        commit(&mut jdb, 1, keccak(b"1a"), Some((0, keccak(b"0"))));
+        jdb.insert(b"shared");
+        let b = jdb.insert(b"only in b");
+        commit(&mut jdb, 1, keccak(b"1b"), Some((0, keccak(b"0"))));
+        assert!(jdb.contains(&a) && jdb.contains(&b));
+
+        commit(&mut jdb, 2, keccak(b"2"), Some((1, keccak(b"1b"))));
+        assert!(!jdb.contains(&a));
+        assert!(jdb.contains(&b) && jdb.contains(&shared));
+    }
+
+    #[test]
+    fn test_state_roots_stay_readable() {
+        let backing = Arc::new(InMemory::new(2));
+        let mut blocks: Vec<(H256, H256)> = vec![];
+        {
+            let mut jdb = recent(&backing, 2);
+            let mut root = *TrieDBMut::new(&mut jdb).root();
+            for now in 0..6u64 {
+                {
+                    let mut trie = TrieDBMut::from_existing(&mut jdb, root).unwrap();
+                    trie.insert(b"counter", &[now as u8; 40]).unwrap();
+                    trie.insert(&now.to_be_bytes(), b"block").unwrap();
+                    root = *trie.root();
+                }
+                let id = keccak(now.to_be_bytes());
+                let end = jdb.finalized_era(now).map(|era| (era, blocks[era as usize].0));
+                commit(&mut jdb, now, id, end);
+                blocks.push((id, root));
+            }
+        }
+
+        // reopened, the journal of the last blocks is picked up again
+        let jdb = recent(&backing, 2);
+        assert_eq!(jdb.latest_era(), Some(5));
+        for (now, &(_, root)) in blocks.iter().enumerate().skip(3) {
+            let trie = TrieDB::new(&jdb, root).unwrap();
+            assert_eq!(trie.get(b"counter").unwrap(), Some(vec![now as u8; 40]));
+        }
+        assert!(TrieDB::new(&jdb, blocks[0].1).is_err());
+    }
+}
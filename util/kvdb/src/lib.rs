@@ -0,0 +1,95 @@
+//! Key-value storage split into columns, written in atomic batches. Comes
+//! with an in-memory database for tests and an append-only log on disk.
+
+#[cfg(test)]
+extern crate tempdir;
+
+mod log;
+mod memory;
+
+use std::io;
+
+pub use log::LogDB;
+pub use memory::InMemory;
+
+pub type DBValue = Vec<u8>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DBOp {
+    Insert {
+        col: u32,
+        key: Vec<u8>,
+        value: DBValue,
+    },
+    Delete {
+        col: u32,
+        key: Vec<u8>,
+    },
+}
+
+impl DBOp {
+    pub fn col(&self) -> u32 {
+        match *self {
+            DBOp::Insert { col, .. } | DBOp::Delete { col, .. } => col,
+        }
+    }
+}
+
+/// Changes applied together by `KeyValueDB::write`, later operations win.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DBTransaction {
+    pub ops: Vec<DBOp>,
+}
+
+impl DBTransaction {
+    pub fn new() -> Self {
+        DBTransaction::default()
+    }
+
+    pub fn put(&mut self, col: u32, key: &[u8], value: &[u8]) {
+        self.ops.push(DBOp::Insert {
+            col,
+            key: key.to_vec(),
+            value: value.to_vec(),
+        });
+    }
+
+    pub fn delete(&mut self, col: u32, key: &[u8]) {
+        self.ops.push(DBOp::Delete { col, key: key.to_vec() });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+pub type KeyValueIter<'a> = Box<dyn Iterator<Item = (Vec<u8>, DBValue)> + 'a>;
+
+pub trait KeyValueDB: Send + Sync {
+    fn num_columns(&self) -> u32;
+
+    fn get(&self, col: u32, key: &[u8]) -> io::Result<Option<DBValue>>;
+
+    /// Applies every operation in `transaction` or, on error, none of them.
+    fn write(&self, transaction: DBTransaction) -> io::Result<()>;
+
+    /// Entries of a column in key order.
+    fn iter(&self, col: u32) -> io::Result<KeyValueIter<'_>>;
+
+    /// Entries of a column whose key starts with `prefix`, in key order.
+    fn iter_with_prefix(&self, col: u32, prefix: &[u8]) -> io::Result<KeyValueIter<'_>> {
+        let prefix = prefix.to_vec();
+        Ok(Box::new(self.iter(col)?.filter(move |(key, _)| key.starts_with(&prefix))))
+    }
+}
+
+fn check_columns(transaction: &DBTransaction, columns: u32) -> io::Result<()> {
+    match transaction.ops.iter().find(|op| op.col() >= columns) {
+        Some(op) => Err(unknown_column(op.col())),
+        None => Ok(()),
+    }
+}
+
+fn unknown_column(col: u32) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("unknown column {}", col))
+}
@@ -0,0 +1,567 @@
+//! On-disk `KeyValueDB`: every batch is appended to a single log file as one
+//! checksummed record, and an in-memory index maps each live key to where
+//! its value sits in the log. A torn record at the end of the log, left by a
+//! crash mid-write, is dropped when the database is opened again, a corrupt
+//! record anywhere else fails the open.
+//!
+//! Record layout: `len: u32 | crc32(len): u32 | crc32(payload): u32 |
+//! payload`, all integers little endian. The length has a checksum of its
+//! own, so a corrupt length is told apart from a record cut short by the end
+//! of the file. The payload is a list of operations,
+//! `1 | col: u32 | key_len: u32 | key | value_len: u32 | value` for an insert
+//! and `0 | col: u32 | key_len: u32 | key` for a delete.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use {check_columns, unknown_column, DBOp, DBTransaction, DBValue, KeyValueDB, KeyValueIter};
+
+const MAGIC: &[u8; 8] = b"LCKVLOG1";
+const LOG_FILE: &str = "db.log";
+const COMPACT_FILE: &str = "db.log.compact";
+const RECORD_HEADER_SIZE: u64 = 12;
+/// Largest record payload, its length has to fit the `u32` length field.
+const MAX_PAYLOAD_SIZE: usize = u32::MAX as usize;
+const OP_DELETE: u8 = 0;
+const OP_INSERT: u8 = 1;
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// `len` as a `u32` length field, keys, values and records of 4 GiB or
+/// more can't be stored.
+fn length_field(len: usize, what: &str) -> io::Result<u32> {
+    if len > u32::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} of {} bytes is too large for the log", what, len),
+        ));
+    }
+    Ok(len as u32)
+}
+
+/// Where a value lives in the log.
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    offset: u64,
+    len: u32,
+}
+
+type Index = Vec<BTreeMap<Vec<u8>, Location>>;
+
+/// Bytes `op` takes up in a payload.
+fn encoded_size(op: &DBOp) -> usize {
+    match *op {
+        DBOp::Insert { ref key, ref value, .. } => 13 + key.len() + value.len(),
+        DBOp::Delete { ref key, .. } => 9 + key.len(),
+    }
+}
+
+fn encode_payload(ops: &[DBOp]) -> io::Result<Vec<u8>> {
+    let mut payload = vec![];
+    for op in ops {
+        match *op {
+            DBOp::Insert { col, ref key, ref value } => {
+                payload.push(OP_INSERT);
+                payload.extend_from_slice(&col.to_le_bytes());
+                payload.extend_from_slice(&length_field(key.len(), "key")?.to_le_bytes());
+                payload.extend_from_slice(key);
+                payload.extend_from_slice(&length_field(value.len(), "value")?.to_le_bytes());
+                payload.extend_from_slice(value);
+            }
+            DBOp::Delete { col, ref key } => {
+                payload.push(OP_DELETE);
+                payload.extend_from_slice(&col.to_le_bytes());
+                payload.extend_from_slice(&length_field(key.len(), "key")?.to_le_bytes());
+                payload.extend_from_slice(key);
+            }
+        }
+    }
+    Ok(payload)
+}
+
+struct PayloadReader<'a> {
+    payload: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PayloadReader<'a> {
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.payload.len() - self.pos < len {
+            return Err(invalid_data("truncated operation"));
+        }
+        let bytes = &self.payload[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+}
+
+/// Applies a record payload found at `offset` in the log to `index`.
+fn index_payload(index: &mut Index, payload: &[u8], offset: u64) -> io::Result<()> {
+    // check the whole record before touching the index
+    let mut changes = vec![];
+    let mut reader = PayloadReader { payload, pos: 0 };
+    while reader.pos < payload.len() {
+        let kind = reader.bytes(1)?[0];
+        let col = reader.u32()?;
+        if col as usize >= index.len() {
+            return Err(unknown_column(col));
+        }
+        let key_len = reader.u32()? as usize;
+        let key = reader.bytes(key_len)?.to_vec();
+        let location = match kind {
+            OP_INSERT => {
+                let len = reader.u32()?;
+                let location = Location {
+                    offset: offset + reader.pos as u64,
+                    len,
+                };
+                reader.bytes(len as usize)?;
+                Some(location)
+            }
+            OP_DELETE => None,
+            _ => return Err(invalid_data("unknown operation")),
+        };
+        changes.push((col, key, location));
+    }
+    for (col, key, location) in changes {
+        match location {
+            Some(location) => index[col as usize].insert(key, location),
+            None => index[col as usize].remove(&key),
+        };
+    }
+    Ok(())
+}
+
+fn header_field(header: &[u8], index: usize) -> u32 {
+    let mut field = [0u8; 4];
+    field.copy_from_slice(&header[index * 4..index * 4 + 4]);
+    u32::from_le_bytes(field)
+}
+
+/// Reads the record at `offset` of a log `file_len` bytes long. `None` at
+/// the end of the log, or when the record there is torn: its header or
+/// payload is cut short by the end of the file, or it ends exactly there and
+/// fails its checksum. Any other bad record is corruption, dropping it would
+/// lose every later batch too.
+fn read_record<R: Read>(reader: &mut R, offset: u64, file_len: u64) -> io::Result<Option<Vec<u8>>> {
+    let remaining = file_len.saturating_sub(offset);
+    if remaining < RECORD_HEADER_SIZE {
+        return Ok(None);
+    }
+    let mut header = [0u8; RECORD_HEADER_SIZE as usize];
+    reader.read_exact(&mut header)?;
+    let len = header_field(&header, 0);
+    if crc32(&header[..4]) != header_field(&header, 1) {
+        return Err(invalid_data("corrupt record length"));
+    }
+    let record_size = RECORD_HEADER_SIZE + u64::from(len);
+    if record_size > remaining {
+        return Ok(None);
+    }
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload)?;
+    if crc32(&payload) != header_field(&header, 2) {
+        if record_size == remaining {
+            return Ok(None);
+        }
+        return Err(invalid_data("corrupt record before the end of the log"));
+    }
+    Ok(Some(payload))
+}
+
+struct Inner {
+    file: File,
+    index: Index,
+    /// End of the last complete record.
+    end: u64,
+}
+
+impl Inner {
+    fn read_value(&mut self, location: Location) -> io::Result<DBValue> {
+        let mut value = vec![0; location.len as usize];
+        self.file.seek(SeekFrom::Start(location.offset))?;
+        self.file.read_exact(&mut value)?;
+        Ok(value)
+    }
+
+    /// Writes `payload` as a record after the last one and moves `end`
+    /// past it.
+    fn append(&mut self, payload: &[u8]) -> io::Result<()> {
+        let len = length_field(payload.len(), "batch")?;
+        let mut record = Vec::with_capacity(RECORD_HEADER_SIZE as usize + payload.len());
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(&crc32(&len.to_le_bytes()).to_le_bytes());
+        record.extend_from_slice(&crc32(payload).to_le_bytes());
+        record.extend_from_slice(payload);
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file.write_all(&record)?;
+        self.file.sync_data()?;
+        self.end += record.len() as u64;
+        Ok(())
+    }
+}
+
+/// Opens the log at `path`, replaying it into a fresh index.
+fn load(path: &Path, num_columns: u32) -> io::Result<Inner> {
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+    if file.metadata()?.len() == 0 {
+        file.write_all(MAGIC)?;
+        file.sync_data()?;
+    }
+
+    let mut index = vec![BTreeMap::new(); num_columns as usize];
+    let mut end = MAGIC.len() as u64;
+    let file_len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(0))?;
+    {
+        let mut reader = BufReader::new(&file);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != *MAGIC {
+            return Err(invalid_data("not a key-value log"));
+        }
+        while let Some(payload) = read_record(&mut reader, end, file_len)? {
+            index_payload(&mut index, &payload, end + RECORD_HEADER_SIZE)?;
+            end += RECORD_HEADER_SIZE + payload.len() as u64;
+        }
+    }
+    if file_len != end {
+        file.set_len(end)?;
+        file.sync_data()?;
+    }
+    Ok(Inner { file, index, end })
+}
+
+pub struct LogDB {
+    dir: PathBuf,
+    inner: Mutex<Inner>,
+}
+
+impl LogDB {
+    /// Opens or creates the database in directory `dir`.
+    pub fn open(dir: &Path, num_columns: u32) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let inner = load(&dir.join(LOG_FILE), num_columns)?;
+        Ok(LogDB {
+            dir: dir.to_path_buf(),
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// Size of the log in bytes.
+    pub fn log_size(&self) -> u64 {
+        self.inner.lock().unwrap().end
+    }
+
+    /// Rewrites the log with only the live entries, reclaiming the space of
+    /// overwritten and deleted values.
+    pub fn compact(&self) -> io::Result<()> {
+        self.compact_into_records(MAX_PAYLOAD_SIZE)
+    }
+
+    /// `compact` with live entries split over records of at most
+    /// `max_payload_size` bytes, or a single entry when that is larger.
+    fn compact_into_records(&self, max_payload_size: usize) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let compact_path = self.dir.join(COMPACT_FILE);
+        let _ = fs::remove_file(&compact_path);
+        let mut compacted = load(&compact_path, inner.index.len() as u32)?;
+
+        let mut ops = vec![];
+        let mut payload_size = 0;
+        for col in 0..inner.index.len() {
+            let entries: Vec<_> = inner.index[col].iter().map(|(key, location)| (key.clone(), *location)).collect();
+            for (key, location) in entries {
+                let op = DBOp::Insert {
+                    col: col as u32,
+                    key,
+                    value: inner.read_value(location)?,
+                };
+                if !ops.is_empty() && payload_size + encoded_size(&op) > max_payload_size {
+                    compacted.append(&encode_payload(&ops)?)?;
+                    ops.clear();
+                    payload_size = 0;
+                }
+                payload_size += encoded_size(&op);
+                ops.push(op);
+            }
+        }
+        if !ops.is_empty() {
+            compacted.append(&encode_payload(&ops)?)?;
+        }
+        drop(compacted);
+        fs::rename(&compact_path, self.dir.join(LOG_FILE))?;
+        *inner = load(&self.dir.join(LOG_FILE), inner.index.len() as u32)?;
+        Ok(())
+    }
+}
+
+impl KeyValueDB for LogDB {
+    fn num_columns(&self) -> u32 {
+        self.inner.lock().unwrap().index.len() as u32
+    }
+
+    fn get(&self, col: u32, key: &[u8]) -> io::Result<Option<DBValue>> {
+        let mut inner = self.inner.lock().unwrap();
+        let location = inner
+            .index
+            .get(col as usize)
+            .ok_or_else(|| unknown_column(col))?
+            .get(key)
+            .cloned();
+        match location {
+            Some(location) => inner.read_value(location).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn write(&self, transaction: DBTransaction) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        check_columns(&transaction, inner.index.len() as u32)?;
+        if transaction.is_empty() {
+            return Ok(());
+        }
+        let payload = encode_payload(&transaction.ops)?;
+        let offset = inner.end + RECORD_HEADER_SIZE;
+        if let Err(e) = inner.append(&payload) {
+            // leave no partial record behind for the next batch to follow
+            let end = inner.end;
+            let _ = inner.file.set_len(end);
+            return Err(e);
+        }
+        index_payload(&mut inner.index, &payload, offset)
+    }
+
+    fn iter(&self, col: u32) -> io::Result<KeyValueIter<'_>> {
+        let mut inner = self.inner.lock().unwrap();
+        let locations: Vec<_> = inner
+            .index
+            .get(col as usize)
+            .ok_or_else(|| unknown_column(col))?
+            .iter()
+            .map(|(key, location)| (key.clone(), *location))
+            .collect();
+        let mut entries = Vec::with_capacity(locations.len());
+        for (key, location) in locations {
+            entries.push((key, inner.read_value(location)?));
+        }
+        Ok(Box::new(entries.into_iter()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+    use std::path::Path;
+    use tempdir::TempDir;
+    use {DBTransaction, KeyValueDB};
+    use super::{crc32, read_record, LogDB, LOG_FILE, MAGIC, RECORD_HEADER_SIZE};
+
+    fn record_count(path: &Path) -> usize {
+        let file = File::open(path).unwrap();
+        let file_len = file.metadata().unwrap().len();
+        let mut reader = BufReader::new(file);
+        reader.read_exact(&mut [0u8; 8]).unwrap();
+        let mut offset = MAGIC.len() as u64;
+        let mut count = 0;
+        while let Some(payload) = read_record(&mut reader, offset, file_len).unwrap() {
+            offset += RECORD_HEADER_SIZE + payload.len() as u64;
+            count += 1;
+        }
+        count
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_reopen() {
+        let dir = TempDir::new("kvdb").unwrap();
+        {
+            let db = LogDB::open(dir.path(), 2).unwrap();
+            let mut tx = DBTransaction::new();
+            tx.put(0, b"dog", b"puppy");
+            tx.put(0, b"horse", b"stallion");
+            tx.put(1, b"dog", b"other column");
+            db.write(tx).unwrap();
+            let mut tx = DBTransaction::new();
+            tx.put(0, b"dog", b"hound");
+            tx.delete(0, b"horse");
+            db.write(tx).unwrap();
+        }
+        let db = LogDB::open(dir.path(), 2).unwrap();
+        assert_eq!(db.get(0, b"dog").unwrap(), Some(b"hound".to_vec()));
+        assert_eq!(db.get(0, b"horse").unwrap(), None);
+        assert_eq!(db.get(1, b"dog").unwrap(), Some(b"other column".to_vec()));
+        let entries: Vec<_> = db.iter(1).unwrap().collect();
+        assert_eq!(entries, vec![(b"dog".to_vec(), b"other column".to_vec())]);
+    }
+
+    #[test]
+    fn test_torn_write_is_dropped() {
+        let dir = TempDir::new("kvdb").unwrap();
+        let size = {
+            let db = LogDB::open(dir.path(), 1).unwrap();
+            let mut tx = DBTransaction::new();
+            tx.put(0, b"dog", b"puppy");
+            db.write(tx).unwrap();
+            db.log_size()
+        };
+        // half of a record, as a crash in the middle of a write leaves it
+        let mut file = OpenOptions::new().append(true).open(dir.path().join(LOG_FILE)).unwrap();
+        file.write_all(&20u32.to_le_bytes()).unwrap();
+        file.write_all(&crc32(&20u32.to_le_bytes()).to_le_bytes()).unwrap();
+        file.write_all(&[1, 2, 3, 4, 1, 0]).unwrap();
+        drop(file);
+
+        let db = LogDB::open(dir.path(), 1).unwrap();
+        assert_eq!(db.log_size(), size);
+        assert_eq!(db.get(0, b"dog").unwrap(), Some(b"puppy".to_vec()));
+        let mut tx = DBTransaction::new();
+        tx.put(0, b"cat", b"kitten");
+        db.write(tx).unwrap();
+        drop(db);
+        let db = LogDB::open(dir.path(), 1).unwrap();
+        assert_eq!(db.get(0, b"cat").unwrap(), Some(b"kitten".to_vec()));
+    }
+
+    /// Writes two batches and overwrites the log at `offset` with `bytes`.
+    fn corrupt_first_record(dir: &Path, offset: u64, bytes: &[u8]) {
+        {
+            let db = LogDB::open(dir, 1).unwrap();
+            let mut tx = DBTransaction::new();
+            tx.put(0, b"dog", b"puppy");
+            db.write(tx).unwrap();
+            let mut tx = DBTransaction::new();
+            tx.put(0, b"cat", b"kitten");
+            db.write(tx).unwrap();
+        }
+        let mut file = OpenOptions::new().read(true).write(true).open(dir.join(LOG_FILE)).unwrap();
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.write_all(bytes).unwrap();
+    }
+
+    fn assert_open_fails(dir: &Path) {
+        let len = dir.join(LOG_FILE).metadata().unwrap().len();
+        let err = LogDB::open(dir, 1).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // the log is left as it was rather than truncated
+        assert_eq!(dir.join(LOG_FILE).metadata().unwrap().len(), len);
+    }
+
+    #[test]
+    fn test_corrupt_record_before_the_end() {
+        let dir = TempDir::new("kvdb").unwrap();
+        // the last byte of the first record's value
+        corrupt_first_record(dir.path(), 8 + RECORD_HEADER_SIZE + 13 + 3 + 4, b"Y");
+        assert_open_fails(dir.path());
+    }
+
+    #[test]
+    fn test_corrupt_length_before_the_end() {
+        let dir = TempDir::new("kvdb").unwrap();
+        // a length running past the end of the log, as a torn record would
+        corrupt_first_record(dir.path(), 8 + 3, &[0x80]);
+        assert_open_fails(dir.path());
+    }
+
+    #[test]
+    fn test_corrupt_last_record_is_dropped() {
+        let dir = TempDir::new("kvdb").unwrap();
+        let size = {
+            let db = LogDB::open(dir.path(), 1).unwrap();
+            let mut tx = DBTransaction::new();
+            tx.put(0, b"dog", b"puppy");
+            db.write(tx).unwrap();
+            let size = db.log_size();
+            let mut tx = DBTransaction::new();
+            tx.put(0, b"cat", b"kitten");
+            db.write(tx).unwrap();
+            size
+        };
+        let mut file = OpenOptions::new().append(true).open(dir.path().join(LOG_FILE)).unwrap();
+        let len = file.metadata().unwrap().len();
+        file.set_len(len - 1).unwrap();
+        file.write_all(b"X").unwrap();
+        drop(file);
+
+        let db = LogDB::open(dir.path(), 1).unwrap();
+        assert_eq!(db.log_size(), size);
+        assert_eq!(db.get(0, b"dog").unwrap(), Some(b"puppy".to_vec()));
+        assert_eq!(db.get(0, b"cat").unwrap(), None);
+    }
+
+    #[test]
+    fn test_compact() {
+        let dir = TempDir::new("kvdb").unwrap();
+        let db = LogDB::open(dir.path(), 1).unwrap();
+        for i in 0..100u32 {
+            let mut tx = DBTransaction::new();
+            tx.put(0, b"counter", &i.to_le_bytes());
+            tx.put(0, &i.to_be_bytes(), b"value");
+            if i % 2 == 1 {
+                tx.delete(0, &(i - 1).to_be_bytes());
+            }
+            db.write(tx).unwrap();
+        }
+        let size = db.log_size();
+        db.compact().unwrap();
+        assert!(db.log_size() < size / 2);
+        assert_eq!(db.get(0, b"counter").unwrap(), Some(99u32.to_le_bytes().to_vec()));
+        assert_eq!(db.iter(0).unwrap().count(), 51);
+        drop(db);
+        let db = LogDB::open(dir.path(), 1).unwrap();
+        assert_eq!(db.get(0, &99u32.to_be_bytes()).unwrap(), Some(b"value".to_vec()));
+        assert_eq!(db.get(0, &98u32.to_be_bytes()).unwrap(), None);
+        assert_eq!(record_count(&dir.path().join(LOG_FILE)), 1);
+    }
+
+    #[test]
+    fn test_compact_splits_records() {
+        let dir = TempDir::new("kvdb").unwrap();
+        let db = LogDB::open(dir.path(), 2).unwrap();
+        let mut tx = DBTransaction::new();
+        for i in 0..10u32 {
+            tx.put(i % 2, &i.to_be_bytes(), &[i as u8; 20]);
+        }
+        db.write(tx).unwrap();
+        assert_eq!(record_count(&dir.path().join(LOG_FILE)), 1);
+
+        // each insert takes 13 + 4 + 20 bytes, three fit in 120
+        db.compact_into_records(120).unwrap();
+        assert_eq!(record_count(&dir.path().join(LOG_FILE)), 4);
+        // a limit below one entry still writes every entry
+        db.compact_into_records(1).unwrap();
+        assert_eq!(record_count(&dir.path().join(LOG_FILE)), 10);
+        drop(db);
+
+        let db = LogDB::open(dir.path(), 2).unwrap();
+        for i in 0..10u32 {
+            assert_eq!(db.get(i % 2, &i.to_be_bytes()).unwrap(), Some(vec![i as u8; 20]));
+        }
+    }
+}
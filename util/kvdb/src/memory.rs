@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::RwLock;
+use {check_columns, unknown_column, DBOp, DBTransaction, DBValue, KeyValueDB, KeyValueIter};
+
+/// `KeyValueDB` kept in memory, for tests.
+pub struct InMemory {
+    columns: RwLock<Vec<BTreeMap<Vec<u8>, DBValue>>>,
+}
+
+impl InMemory {
+    pub fn new(num_columns: u32) -> Self {
+        InMemory {
+            columns: RwLock::new(vec![BTreeMap::new(); num_columns as usize]),
+        }
+    }
+}
+
+impl KeyValueDB for InMemory {
+    fn num_columns(&self) -> u32 {
+        self.columns.read().unwrap().len() as u32
+    }
+
+    fn get(&self, col: u32, key: &[u8]) -> io::Result<Option<DBValue>> {
+        let columns = self.columns.read().unwrap();
+        let column = columns.get(col as usize).ok_or_else(|| unknown_column(col))?;
+        Ok(column.get(key).cloned())
+    }
+
+    fn write(&self, transaction: DBTransaction) -> io::Result<()> {
+        let mut columns = self.columns.write().unwrap();
+        check_columns(&transaction, columns.len() as u32)?;
+        for op in transaction.ops {
+            match op {
+                DBOp::Insert { col, key, value } => {
+                    columns[col as usize].insert(key, value);
+                }
+                DBOp::Delete { col, key } => {
+                    columns[col as usize].remove(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn iter(&self, col: u32) -> io::Result<KeyValueIter<'_>> {
+        let columns = self.columns.read().unwrap();
+        let column = columns.get(col as usize).ok_or_else(|| unknown_column(col))?;
+        let entries: Vec<_> = column.iter().map(|(key, value)| (key.clone(), value.clone())).collect();
+        Ok(Box::new(entries.into_iter()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DBTransaction, KeyValueDB};
+    use super::InMemory;
+
+    #[test]
+    fn test_in_memory() {
+        let db = InMemory::new(2);
+        let mut tx = DBTransaction::new();
+        tx.put(0, b"dog", b"puppy");
+        tx.put(0, b"doge", b"coin");
+        tx.put(1, b"dog", b"other column");
+        tx.put(0, b"cat", b"kitten");
+        tx.delete(0, b"cat");
+        db.write(tx).unwrap();
+        assert_eq!(db.get(0, b"dog").unwrap(), Some(b"puppy".to_vec()));
+        assert_eq!(db.get(1, b"dog").unwrap(), Some(b"other column".to_vec()));
+        assert_eq!(db.get(0, b"cat").unwrap(), None);
+        let keys: Vec<_> = db.iter_with_prefix(0, b"do").unwrap().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec![b"dog".to_vec(), b"doge".to_vec()]);
+
+        // a batch touching an unknown column changes nothing
+        let mut tx = DBTransaction::new();
+        tx.put(0, b"horse", b"stallion");
+        tx.put(2, b"horse", b"stallion");
+        assert!(db.write(tx).is_err());
+        assert_eq!(db.get(0, b"horse").unwrap(), None);
+        assert!(db.get(2, b"horse").is_err());
+    }
+}